@@ -0,0 +1,78 @@
+//! This benchmark measures the conversion of deep type-level patterns to their term-level representation.
+//! `ToIOPattern::to_iopattern` borrows the words computed at compile time, and its cost is independent of the depth
+//! of the pattern; iterating over the words is linear. For comparison, the former implementation, which prepended
+//! each word to the vector of the tail, is quadratic.
+//!
+//! Run with `cargo bench --bench patterns`.
+
+// the List bound on the tail of `Cons` is checked recursively over the depth of the pattern
+#![recursion_limit = "2048"]
+
+use std::hint::black_box;
+use std::iter;
+use std::time::{Duration, Instant};
+
+use extra_safe::traits::{Absorb, Cons, List, Nil, Squeeze};
+use extra_safe::{SpongeOp, ToIOPattern, ToSpongeOp};
+use typenum::U1;
+
+// Patterns of 2^k words, alternating absorbs and squeezes so that they are already normalized
+type P2<T> = Cons<Absorb<U1>, Cons<Squeeze<U1>, T>>;
+type P8<T> = P2<P2<P2<P2<T>>>>;
+type P32<T> = P8<P8<P8<P8<T>>>>;
+type P128<T> = P32<P32<P32<P32<T>>>>;
+type P512<T> = P128<P128<P128<P128<T>>>>;
+
+// The former conversion, which allocated a vector per word of the pattern
+trait Prepending {
+    fn to_vec() -> Vec<SpongeOp>;
+}
+
+impl Prepending for Nil {
+    fn to_vec() -> Vec<SpongeOp> {
+        vec![]
+    }
+}
+
+impl<Item: ToSpongeOp, T: List + Prepending> Prepending for Cons<Item, T> {
+    fn to_vec() -> Vec<SpongeOp> {
+        iter::once(Item::SPONGE_OP).chain(T::to_vec()).collect()
+    }
+}
+
+// Returns the average time of a call to `f`, over enough iterations to last a few milliseconds
+fn time<R>(mut f: impl FnMut() -> R) -> Duration {
+    let mut iterations = 1;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            black_box(f());
+        }
+        let elapsed = start.elapsed();
+        if elapsed > Duration::from_millis(20) {
+            return elapsed / iterations;
+        }
+        iterations *= 2;
+    }
+}
+
+fn bench<P: ToIOPattern + Prepending>() {
+    let convert = time(P::to_iopattern);
+    let iterate = time(|| P::to_iopattern().iter().count());
+    let prepend = time(P::to_vec);
+    println!(
+        "{:>5} words: to_iopattern {:>10?}, iteration {:>10?}, prepending {:>10?}",
+        P::LEN,
+        convert,
+        iterate,
+        prepend
+    );
+}
+
+fn main() {
+    bench::<P8<Nil>>();
+    bench::<P32<Nil>>();
+    bench::<P128<Nil>>();
+    bench::<P512<Nil>>();
+    bench::<P512<P512<Nil>>>();
+}
@@ -0,0 +1,178 @@
+//! This module contains the byte-oriented layer on top of the sponge values.
+//! Many inputs to a sponge (hashes, serialized messages) are bytes, whereas [`crate::SpongeAPI::Value`]
+//! is usually a field element. The [`BytePacking`] trait describes how many bytes fit in a single
+//! element, and [`PackedLength`] computes, at the type level, how many elements a given number of bytes
+//! occupies. This lets byte-oriented calls such as [`crate::ExtraSponge::absorb_bytes`] be checked against
+//! IOPatterns expressed in element counts.
+//!
+//! The [`CanonicalValue`] trait, on the other hand, describes the canonical (injective, fixed-width) byte
+//! encoding of a value, as needed for serialization and test vectors.
+
+use core::ops::{Add, Div, Sub};
+
+use hybrid_array::{Array, ArraySize};
+use typenum::{NonZero, Quot, Sub1, Sum, Unsigned, B1, U1, U4, U8};
+
+use crate::Error;
+
+/// A value which can hold a fixed number of bytes, injectively.
+/// Bytes are packed in little-endian order, so that a short input is equivalent to
+/// the same input right-padded with zeroes: packing does not tell the two apart, see
+/// [`crate::ExtraSponge::absorb_bytes`].
+pub trait BytePacking: Sized {
+    /// The number of bytes packed in a single value
+    type Width: Unsigned + NonZero;
+
+    /// Packs at most `Self::Width` bytes into a value.
+    fn pack(bytes: &[u8]) -> Self;
+
+    /// Unpacks the value into at most `Self::Width` bytes, truncating the output to the length of `bytes`.
+    fn unpack(&self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_byte_packing {
+    ($($ty:ty => $width:ty),+) => {
+        $(
+            impl BytePacking for $ty {
+                type Width = $width;
+
+                fn pack(bytes: &[u8]) -> Self {
+                    assert!(bytes.len() <= <$width>::USIZE, "too many bytes to pack");
+                    let mut buf = [0u8; <$width>::USIZE];
+                    buf[..bytes.len()].copy_from_slice(bytes);
+                    <$ty>::from_le_bytes(buf)
+                }
+
+                fn unpack(&self, bytes: &mut [u8]) {
+                    assert!(bytes.len() <= <$width>::USIZE, "too many bytes to unpack");
+                    let len = bytes.len();
+                    bytes.copy_from_slice(&self.to_le_bytes()[..len]);
+                }
+            }
+        )+
+    };
+}
+
+impl_byte_packing! {
+    u8 => U1,
+    u32 => U4,
+    u64 => U8
+}
+
+/// A value with a canonical byte encoding of fixed width.
+/// The encoding is injective: `from_bytes` accepts exactly the outputs of `to_bytes`, and
+/// returns an error on any other input (e.g. an out-of-range field element).
+pub trait CanonicalValue: Sized {
+    /// The width of the encoding, in bytes
+    type Bytes: ArraySize<u8>;
+
+    /// Returns the canonical encoding of the value.
+    fn to_bytes(&self) -> Array<u8, Self::Bytes>;
+
+    /// Decodes a value from its canonical encoding, checking the encoding is in range.
+    fn from_bytes(bytes: &Array<u8, Self::Bytes>) -> Result<Self, Error>;
+}
+
+macro_rules! impl_canonical_value {
+    ($($ty:ty => $width:ty),+) => {
+        $(
+            // All byte strings of the right width are canonical encodings of an integer
+            impl CanonicalValue for $ty {
+                type Bytes = $width;
+
+                fn to_bytes(&self) -> Array<u8, Self::Bytes> {
+                    Array(self.to_le_bytes())
+                }
+
+                fn from_bytes(bytes: &Array<u8, Self::Bytes>) -> Result<Self, Error> {
+                    Ok(<$ty>::from_le_bytes(bytes.0))
+                }
+            }
+        )+
+    };
+}
+
+impl_canonical_value! {
+    u8 => U1,
+    u32 => U4,
+    u64 => U8
+}
+
+/// Type-level computation of the number of values of byte width `W` required to pack `Self` bytes,
+/// i.e. the ceiling of `Self / W`.
+pub trait PackedLength<W> {
+    /// The number of values
+    type Output: Unsigned;
+}
+
+impl<N, W> PackedLength<W> for N
+where
+    W: Unsigned + NonZero + Sub<B1>,
+    N: Unsigned + Add<Sub1<W>>,
+    Sum<N, Sub1<W>>: Div<W>,
+    Quot<Sum<N, Sub1<W>>, W>: Unsigned,
+{
+    type Output = Quot<Sum<N, Sub1<W>>, W>;
+}
+
+/// Convenience alias for the number of values of type `V` required to pack `N` bytes
+pub type Packed<N, V> = <N as PackedLength<<V as BytePacking>::Width>>::Output;
+
+/// Packs `bytes` into `elements`, `V::Width` bytes at a time.
+pub(crate) fn pack_bytes<V: BytePacking>(bytes: &[u8], elements: &mut [V]) {
+    debug_assert_eq!(elements.len(), bytes.len().div_ceil(V::Width::USIZE));
+    elements
+        .iter_mut()
+        .zip(bytes.chunks(V::Width::USIZE))
+        .for_each(|(element, chunk)| *element = V::pack(chunk));
+}
+
+/// Unpacks `elements` into `bytes`, `V::Width` bytes at a time.
+pub(crate) fn unpack_bytes<V: BytePacking>(elements: &[V], bytes: &mut [u8]) {
+    debug_assert_eq!(elements.len(), bytes.len().div_ceil(V::Width::USIZE));
+    elements
+        .iter()
+        .zip(bytes.chunks_mut(V::Width::USIZE))
+        .for_each(|(element, chunk)| element.unpack(chunk));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use typenum::{assert_type_eq, U0, U2, U3, U5, U7, U9};
+
+    #[test]
+    fn packed_lengths() {
+        assert_type_eq!(Packed<U0, u64>, U0);
+        assert_type_eq!(Packed<U1, u64>, U1);
+        assert_type_eq!(Packed<U8, u64>, U1);
+        assert_type_eq!(Packed<U9, u64>, U2);
+        assert_type_eq!(Packed<U7, u32>, U2);
+        assert_type_eq!(Packed<U9, u32>, U3);
+        assert_type_eq!(Packed<U5, u8>, U5);
+    }
+
+    #[test]
+    fn pack_roundtrip() {
+        let bytes = [1u8, 2, 3, 4, 5, 6, 7, 8, 9];
+        let mut elements = [0u32; 3];
+        pack_bytes(&bytes, &mut elements);
+        assert_eq!(elements, [0x04030201, 0x08070605, 0x09]);
+
+        let mut out = [0u8; 9];
+        unpack_bytes(&elements, &mut out);
+        assert_eq!(out, bytes);
+    }
+
+    #[test]
+    fn canonical_roundtrip() {
+        let x = 0x0807060504030201u64;
+        let bytes = x.to_bytes();
+        assert_eq!(bytes.as_slice(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(u64::from_bytes(&bytes).unwrap(), x);
+
+        let y = 0xdeadbeefu32;
+        assert_eq!(u32::from_bytes(&y.to_bytes()).unwrap(), y);
+        assert_eq!(u8::from_bytes(&42u8.to_bytes()).unwrap(), 42);
+    }
+}
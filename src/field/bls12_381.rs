@@ -0,0 +1,359 @@
+//! The scalar field of the BLS12-381 curve, of 255-bit modulus
+//! `0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001`.
+
+use core::fmt;
+use core::ops::{Add, Mul, Neg, Sub};
+
+use hybrid_array::Array;
+use typenum::{U31, U32};
+
+use super::Field;
+use crate::codec::{BytePacking, CanonicalValue};
+use crate::Error;
+
+// The limbs are little-endian
+const MODULUS: [u64; 4] = [
+    0xffff_ffff_0000_0001,
+    0x53bd_a402_fffe_5bfe,
+    0x3339_d808_09a1_d805,
+    0x73ed_a753_299d_7d48,
+];
+// 2^256 mod MODULUS, the Montgomery form of 1
+const R: [u64; 4] = [
+    0x0000_0001_ffff_fffe,
+    0x5884_b7fa_0003_4802,
+    0x998c_4fef_ecbc_4ff5,
+    0x1824_b159_acc5_056f,
+];
+// 2^512 mod MODULUS, which maps an integer to its Montgomery form
+const R2: [u64; 4] = [
+    0xc999_e990_f3f2_9c6d,
+    0x2b6c_edcb_8792_5c23,
+    0x05d3_1496_7254_398f,
+    0x0748_d9d9_9f59_ff11,
+];
+// -MODULUS^-1 mod 2^64
+const INV: u64 = 0xffff_fffe_ffff_ffff;
+
+/// An element of the scalar field of BLS12-381.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Bls12_381Scalar(
+    // the Montgomery form `a * 2^256 mod MODULUS` of the element `a`, which is below the modulus
+    [u64; 4],
+);
+
+// Returns a + b + carry, and the carry out
+const fn adc(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let res = a as u128 + b as u128 + carry as u128;
+    (res as u64, (res >> 64) as u64)
+}
+
+// Returns a - b - borrow, and the borrow out, which is either 0 or u64::MAX
+const fn sbb(a: u64, b: u64, borrow: u64) -> (u64, u64) {
+    let res = (a as u128).wrapping_sub(b as u128 + (borrow >> 63) as u128);
+    (res as u64, (res >> 64) as u64)
+}
+
+// Returns a + b * c + carry, and the carry out
+const fn mac(a: u64, b: u64, c: u64, carry: u64) -> (u64, u64) {
+    let res = a as u128 + b as u128 * c as u128 + carry as u128;
+    (res as u64, (res >> 64) as u64)
+}
+
+impl Bls12_381Scalar {
+    // Subtracts the modulus from `limbs` if they are not below it, given `limbs` are below twice the modulus,
+    // with `carry` the bit above the limbs.
+    const fn subtract_modulus(limbs: [u64; 4], carry: u64) -> [u64; 4] {
+        let (d0, borrow) = sbb(limbs[0], MODULUS[0], 0);
+        let (d1, borrow) = sbb(limbs[1], MODULUS[1], borrow);
+        let (d2, borrow) = sbb(limbs[2], MODULUS[2], borrow);
+        let (d3, borrow) = sbb(limbs[3], MODULUS[3], borrow);
+        let (_, borrow) = sbb(carry, 0, borrow);
+        if borrow == 0 {
+            [d0, d1, d2, d3]
+        } else {
+            limbs
+        }
+    }
+
+    // Returns a * b * 2^-256 mod MODULUS, by Montgomery multiplication with interleaved reduction.
+    const fn montgomery_mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut t = [0u64; 6];
+        let mut i = 0;
+        while i < 4 {
+            let mut carry = 0;
+            let mut j = 0;
+            while j < 4 {
+                (t[j], carry) = mac(t[j], a[j], b[i], carry);
+                j += 1;
+            }
+            (t[4], t[5]) = adc(t[4], carry, 0);
+
+            let m = t[0].wrapping_mul(INV);
+            let (_, mut carry) = mac(t[0], m, MODULUS[0], 0);
+            let mut j = 1;
+            while j < 4 {
+                (t[j - 1], carry) = mac(t[j], m, MODULUS[j], carry);
+                j += 1;
+            }
+            let (sum, high) = adc(t[4], carry, 0);
+            t[3] = sum;
+            t[4] = t[5] + high;
+            t[5] = 0;
+            i += 1;
+        }
+        Self::subtract_modulus([t[0], t[1], t[2], t[3]], t[4])
+    }
+
+    /// Returns the element of canonical representative `limbs`, given as little-endian 64-bit limbs,
+    /// or `None` if they are not below the modulus.
+    pub const fn from_limbs(limbs: [u64; 4]) -> Option<Bls12_381Scalar> {
+        let (_, borrow) = sbb(limbs[0], MODULUS[0], 0);
+        let (_, borrow) = sbb(limbs[1], MODULUS[1], borrow);
+        let (_, borrow) = sbb(limbs[2], MODULUS[2], borrow);
+        let (_, borrow) = sbb(limbs[3], MODULUS[3], borrow);
+        if borrow == 0 {
+            None
+        } else {
+            Some(Bls12_381Scalar(Self::montgomery_mul(&limbs, &R2)))
+        }
+    }
+
+    /// Returns the canonical representative of the element, as little-endian 64-bit limbs.
+    pub const fn to_limbs(self) -> [u64; 4] {
+        Self::montgomery_mul(&self.0, &[1, 0, 0, 0])
+    }
+}
+
+impl fmt::Debug for Bls12_381Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limbs = self.to_limbs();
+        write!(
+            f,
+            "Bls12_381Scalar(0x{:016x}{:016x}{:016x}{:016x})",
+            limbs[3], limbs[2], limbs[1], limbs[0]
+        )
+    }
+}
+
+impl Add for Bls12_381Scalar {
+    type Output = Bls12_381Scalar;
+
+    fn add(self, rhs: Bls12_381Scalar) -> Bls12_381Scalar {
+        let (d0, carry) = adc(self.0[0], rhs.0[0], 0);
+        let (d1, carry) = adc(self.0[1], rhs.0[1], carry);
+        let (d2, carry) = adc(self.0[2], rhs.0[2], carry);
+        let (d3, carry) = adc(self.0[3], rhs.0[3], carry);
+        Bls12_381Scalar(Self::subtract_modulus([d0, d1, d2, d3], carry))
+    }
+}
+
+impl Sub for Bls12_381Scalar {
+    type Output = Bls12_381Scalar;
+
+    fn sub(self, rhs: Bls12_381Scalar) -> Bls12_381Scalar {
+        let (d0, borrow) = sbb(self.0[0], rhs.0[0], 0);
+        let (d1, borrow) = sbb(self.0[1], rhs.0[1], borrow);
+        let (d2, borrow) = sbb(self.0[2], rhs.0[2], borrow);
+        let (d3, borrow) = sbb(self.0[3], rhs.0[3], borrow);
+        // on underflow, the borrow is all ones, and the modulus is added back
+        let (d0, carry) = adc(d0, MODULUS[0] & borrow, 0);
+        let (d1, carry) = adc(d1, MODULUS[1] & borrow, carry);
+        let (d2, carry) = adc(d2, MODULUS[2] & borrow, carry);
+        let (d3, _) = adc(d3, MODULUS[3] & borrow, carry);
+        Bls12_381Scalar([d0, d1, d2, d3])
+    }
+}
+
+impl Mul for Bls12_381Scalar {
+    type Output = Bls12_381Scalar;
+
+    fn mul(self, rhs: Bls12_381Scalar) -> Bls12_381Scalar {
+        Bls12_381Scalar(Self::montgomery_mul(&self.0, &rhs.0))
+    }
+}
+
+impl Neg for Bls12_381Scalar {
+    type Output = Bls12_381Scalar;
+
+    fn neg(self) -> Bls12_381Scalar {
+        Bls12_381Scalar::ZERO - self
+    }
+}
+
+impl Field for Bls12_381Scalar {
+    const ZERO: Bls12_381Scalar = Bls12_381Scalar([0; 4]);
+    const ONE: Bls12_381Scalar = Bls12_381Scalar(R);
+    const MODULUS: &'static [u64] = &MODULUS;
+    const MODULUS_BITS: u32 = 255;
+    // 7 in Montgomery form
+    const GENERATOR: Bls12_381Scalar = Bls12_381Scalar([
+        0x0000_000e_ffff_fff1,
+        0x17e3_63d3_0018_9c0f,
+        0xff9c_5787_6f84_57b0,
+        0x3513_3220_8fc5_a8c4,
+    ]);
+
+    fn from_u64(n: u64) -> Bls12_381Scalar {
+        Bls12_381Scalar(Self::montgomery_mul(&[n, 0, 0, 0], &R2))
+    }
+
+    fn inverse(&self) -> Option<Bls12_381Scalar> {
+        let mut exp = MODULUS;
+        exp[0] -= 2;
+        (!self.is_zero()).then(|| self.pow(&exp))
+    }
+}
+
+/// Elements are encoded as their canonical representative, in little-endian order.
+impl CanonicalValue for Bls12_381Scalar {
+    type Bytes = U32;
+
+    fn to_bytes(&self) -> Array<u8, U32> {
+        let mut res = [0u8; 32];
+        for (bytes, limb) in res.chunks_exact_mut(8).zip(self.to_limbs()) {
+            bytes.copy_from_slice(&limb.to_le_bytes());
+        }
+        Array(res)
+    }
+
+    fn from_bytes(bytes: &Array<u8, U32>) -> Result<Bls12_381Scalar, Error> {
+        let mut limbs = [0u64; 4];
+        for (limb, bytes) in limbs.iter_mut().zip(bytes.0.chunks_exact(8)) {
+            *limb = u64::from_le_bytes(bytes.try_into().unwrap());
+        }
+        Bls12_381Scalar::from_limbs(limbs).ok_or(Error::NonCanonicalEncoding)
+    }
+}
+
+/// Thirty-one bytes are packed in an element, as they always encode an integer below the modulus.
+/// Unpacking an element which does not hold packed bytes, e.g. a squeezed one, drops its most significant byte.
+impl BytePacking for Bls12_381Scalar {
+    type Width = U31;
+
+    fn pack(bytes: &[u8]) -> Bls12_381Scalar {
+        assert!(bytes.len() <= 31, "too many bytes to pack");
+        let mut buf = [0u8; 32];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Bls12_381Scalar::from_bytes(&Array(buf))
+            .expect("31 bytes encode an integer below the modulus")
+    }
+
+    fn unpack(&self, bytes: &mut [u8]) {
+        assert!(bytes.len() <= 31, "too many bytes to unpack");
+        let len = bytes.len();
+        bytes.copy_from_slice(&self.to_bytes().0[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::tests::{check_field, check_generator};
+    use std::format;
+    use std::prelude::rust_2021::*;
+
+    fn elements() -> Vec<Bls12_381Scalar> {
+        let mut minus_one = MODULUS;
+        minus_one[0] -= 1;
+        let mut elements: Vec<_> = [0, 1, 2, u64::MAX]
+            .into_iter()
+            .map(Bls12_381Scalar::from_u64)
+            .collect();
+        elements.push(Bls12_381Scalar::from_limbs(minus_one).unwrap());
+        elements.push(Bls12_381Scalar::from_u128(u128::MAX));
+        elements.push(
+            Bls12_381Scalar::from_limbs([
+                0x0123_4567_89ab_cdef,
+                0xfedc_ba98_7654_3210,
+                0x0f1e_2d3c_4b5a_6978,
+                0x7000_0000_0000_0000,
+            ])
+            .unwrap(),
+        );
+        elements
+    }
+
+    #[test]
+    fn is_a_field() {
+        check_field(&elements());
+    }
+
+    #[test]
+    fn generates() {
+        assert_eq!(Bls12_381Scalar::GENERATOR, Bls12_381Scalar::from_u64(7));
+        check_generator::<Bls12_381Scalar>(&[
+            2, 3, 11, 19, 10177, 125527, 859267, 906349, 2508409, 2529403, 52437899, 254760293,
+        ]);
+    }
+
+    // The expected values are computed with Python's arbitrary-precision integers
+    #[test]
+    fn computes() {
+        let a = Bls12_381Scalar::from_u128(u128::MAX);
+        assert_eq!(
+            (a * a * a).to_limbs(),
+            [
+                0xcf2a_b214_f81f_7133,
+                0xdca7_6bcd_abfe_e406,
+                0xe150_cda0_b6d6_3932,
+                0x593e_4265_f7d2_a7c4,
+            ]
+        );
+        assert_eq!(
+            Bls12_381Scalar::from_u64(2).inverse().unwrap().to_limbs(),
+            [
+                0x7fff_ffff_8000_0001,
+                0xa9de_d201_7fff_2dff,
+                0x199c_ec04_04d0_ec02,
+                0x39f6_d3a9_94ce_bea4,
+            ]
+        );
+        assert_eq!(
+            format!("{:?}", Bls12_381Scalar::from_u64(0xff)),
+            "Bls12_381Scalar(0x00000000000000000000000000000000000000000000000000000000000000ff)"
+        );
+    }
+
+    #[test]
+    fn encodes() {
+        let a = Bls12_381Scalar::from_u64(0x0102);
+        let mut bytes = [0u8; 32];
+        bytes[..2].copy_from_slice(&[2, 1]);
+        assert_eq!(a.to_bytes().0, bytes);
+
+        let mut modulus = [0u8; 32];
+        for (bytes, limb) in modulus.chunks_exact_mut(8).zip(MODULUS) {
+            bytes.copy_from_slice(&limb.to_le_bytes());
+        }
+        assert!(matches!(
+            Bls12_381Scalar::from_bytes(&Array(modulus)),
+            Err(Error::NonCanonicalEncoding)
+        ));
+        modulus[0] -= 1;
+        assert_eq!(
+            Bls12_381Scalar::from_bytes(&Array(modulus)).unwrap(),
+            -Bls12_381Scalar::ONE
+        );
+    }
+
+    #[test]
+    fn packs() {
+        let a = Bls12_381Scalar::pack(&[0xff; 31]);
+        assert_eq!(
+            a.to_limbs(),
+            [u64::MAX, u64::MAX, u64::MAX, 0x00ff_ffff_ffff_ffff]
+        );
+        let mut bytes = [0u8; 31];
+        a.unpack(&mut bytes);
+        assert_eq!(bytes, [0xff; 31]);
+
+        assert_eq!(
+            Bls12_381Scalar::pack(&[2, 1]),
+            Bls12_381Scalar::from_u64(0x0102)
+        );
+        let mut bytes = [0u8; 2];
+        (-Bls12_381Scalar::ONE).unpack(&mut bytes);
+        assert_eq!(bytes, [0, 0]);
+    }
+}
@@ -0,0 +1,231 @@
+//! The Goldilocks field, of modulus `2^64 - 2^32 + 1`.
+
+use core::fmt;
+use core::ops::{Add, Mul, Neg, Sub};
+
+use hybrid_array::Array;
+use typenum::{U7, U8};
+
+use super::Field;
+use crate::codec::{BytePacking, CanonicalValue};
+use crate::Error;
+
+const MODULUS: u64 = 0xffff_ffff_0000_0001;
+// 2^64 mod MODULUS, so that 2^64 = 2^32 - 1 in the field
+const EPSILON: u64 = 0xffff_ffff;
+
+/// An element of the Goldilocks field, of modulus `2^64 - 2^32 + 1`, which fits in a 64-bit word.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Goldilocks(
+    // always below the modulus
+    u64,
+);
+
+impl Goldilocks {
+    // Reduces a 128-bit integer, using 2^64 = 2^32 - 1 and 2^96 = -1 in the field.
+    fn reduce(n: u128) -> Goldilocks {
+        let low = n as u64;
+        let high = (n >> 64) as u64;
+        let (high_high, high_low) = (high >> 32, high & EPSILON);
+
+        let (mut res, borrow) = low.overflowing_sub(high_high);
+        if borrow {
+            // adding 2^64 = EPSILON back, which can not underflow as res is then above 2^64 - 2^32
+            res = res.wrapping_sub(EPSILON);
+        }
+        let (res, carry) = res.overflowing_add(high_low * EPSILON);
+        Goldilocks::from_u64(res.wrapping_add(EPSILON * carry as u64))
+    }
+
+    /// Returns the canonical representative of the element, below the modulus.
+    pub const fn to_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Debug for Goldilocks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Goldilocks({:#x})", self.0)
+    }
+}
+
+impl Add for Goldilocks {
+    type Output = Goldilocks;
+
+    fn add(self, rhs: Goldilocks) -> Goldilocks {
+        let (res, carry) = self.0.overflowing_add(rhs.0);
+        // on overflow, the sum is res + 2^64 = res + EPSILON, which is below the modulus
+        let res = if carry { res + EPSILON } else { res };
+        Goldilocks::from_u64(res)
+    }
+}
+
+impl Sub for Goldilocks {
+    type Output = Goldilocks;
+
+    fn sub(self, rhs: Goldilocks) -> Goldilocks {
+        let (res, borrow) = self.0.overflowing_sub(rhs.0);
+        Goldilocks(if borrow {
+            res.wrapping_add(MODULUS)
+        } else {
+            res
+        })
+    }
+}
+
+impl Mul for Goldilocks {
+    type Output = Goldilocks;
+
+    fn mul(self, rhs: Goldilocks) -> Goldilocks {
+        Goldilocks::reduce(self.0 as u128 * rhs.0 as u128)
+    }
+}
+
+impl Neg for Goldilocks {
+    type Output = Goldilocks;
+
+    fn neg(self) -> Goldilocks {
+        Goldilocks::ZERO - self
+    }
+}
+
+impl Field for Goldilocks {
+    const ZERO: Goldilocks = Goldilocks(0);
+    const ONE: Goldilocks = Goldilocks(1);
+    const MODULUS: &'static [u64] = &[MODULUS];
+    const MODULUS_BITS: u32 = 64;
+    const GENERATOR: Goldilocks = Goldilocks(7);
+
+    fn from_u64(n: u64) -> Goldilocks {
+        Goldilocks(if n >= MODULUS { n - MODULUS } else { n })
+    }
+
+    fn from_u128(n: u128) -> Goldilocks {
+        Goldilocks::reduce(n)
+    }
+
+    fn inverse(&self) -> Option<Goldilocks> {
+        (!self.is_zero()).then(|| self.pow_u64(MODULUS - 2))
+    }
+}
+
+/// Elements are encoded as their canonical representative, in little-endian order.
+impl CanonicalValue for Goldilocks {
+    type Bytes = U8;
+
+    fn to_bytes(&self) -> Array<u8, U8> {
+        Array(self.0.to_le_bytes())
+    }
+
+    fn from_bytes(bytes: &Array<u8, U8>) -> Result<Goldilocks, Error> {
+        let n = u64::from_le_bytes(bytes.0);
+        if n < MODULUS {
+            Ok(Goldilocks(n))
+        } else {
+            Err(Error::NonCanonicalEncoding)
+        }
+    }
+}
+
+/// Seven bytes are packed in an element, as they always encode an integer below the modulus.
+/// Unpacking an element which does not hold packed bytes, e.g. a squeezed one, drops its most significant byte.
+impl BytePacking for Goldilocks {
+    type Width = U7;
+
+    fn pack(bytes: &[u8]) -> Goldilocks {
+        assert!(bytes.len() <= 7, "too many bytes to pack");
+        let mut buf = [0u8; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        Goldilocks(u64::from_le_bytes(buf))
+    }
+
+    fn unpack(&self, bytes: &mut [u8]) {
+        assert!(bytes.len() <= 7, "too many bytes to unpack");
+        let len = bytes.len();
+        bytes.copy_from_slice(&self.0.to_le_bytes()[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::tests::{check_field, check_generator};
+    use std::format;
+    use std::prelude::rust_2021::*;
+
+    fn elements() -> Vec<Goldilocks> {
+        [
+            0,
+            1,
+            2,
+            EPSILON,
+            EPSILON + 1,
+            MODULUS - 1,
+            MODULUS >> 1,
+            0x1234_5678_9abc_def0,
+        ]
+        .into_iter()
+        .map(Goldilocks::from_u64)
+        .collect()
+    }
+
+    #[test]
+    fn is_a_field() {
+        check_field(&elements());
+    }
+
+    #[test]
+    fn generates() {
+        check_generator::<Goldilocks>(&[2, 3, 5, 17, 257, 65537]);
+    }
+
+    #[test]
+    fn reduces() {
+        let mut n: u128 = 0x5afe;
+        for _ in 0..1000 {
+            assert_eq!(
+                Goldilocks::from_u128(n).to_u64() as u128,
+                n % MODULUS as u128
+            );
+            n = n.wrapping_mul(0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835) ^ (n >> 67);
+        }
+        assert_eq!(
+            Goldilocks::from_u128(u128::MAX).to_u64() as u128,
+            u128::MAX % MODULUS as u128
+        );
+        assert_eq!(Goldilocks::from_u64(u64::MAX).to_u64(), u64::MAX - MODULUS);
+        for a in elements() {
+            for b in elements() {
+                let product = a.to_u64() as u128 * b.to_u64() as u128 % MODULUS as u128;
+                assert_eq!((a * b).to_u64() as u128, product);
+                let sum = (a.to_u64() as u128 + b.to_u64() as u128) % MODULUS as u128;
+                assert_eq!((a + b).to_u64() as u128, sum);
+            }
+        }
+    }
+
+    #[test]
+    fn encodes() {
+        let a = Goldilocks::from_u64(0x0102_0304_0506_0708);
+        assert_eq!(a.to_bytes().0, [8, 7, 6, 5, 4, 3, 2, 1]);
+        assert!(matches!(
+            Goldilocks::from_bytes(&Array(MODULUS.to_le_bytes())),
+            Err(Error::NonCanonicalEncoding)
+        ));
+        assert_eq!(format!("{a:?}"), "Goldilocks(0x102030405060708)");
+    }
+
+    #[test]
+    fn packs() {
+        let a = Goldilocks::pack(&[0xff; 7]);
+        assert_eq!(a.to_u64(), (1 << 56) - 1);
+        let mut bytes = [0u8; 7];
+        a.unpack(&mut bytes);
+        assert_eq!(bytes, [0xff; 7]);
+
+        assert_eq!(Goldilocks::pack(&[1, 2]), Goldilocks::from_u64(0x0201));
+        let mut bytes = [0u8; 3];
+        Goldilocks::from_u64(0x0102_0304_0506_0708).unpack(&mut bytes);
+        assert_eq!(bytes, [8, 7, 6]);
+    }
+}
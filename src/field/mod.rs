@@ -0,0 +1,249 @@
+//! This module contains a minimal prime field abstraction, enough to build and test sponges over field elements
+//! without depending on an external field crate.
+//! The [`Field`] trait offers the arithmetic of the field, and its elements have a canonical byte encoding through
+//! [`CanonicalValue`]. Two fields are implemented: the 64-bit [`Goldilocks`] field, and the 255-bit scalar field of
+//! BLS12-381, [`Bls12_381Scalar`]. The elements of both fields also pack bytes, see [`crate::codec::BytePacking`].
+//!
+//! Any field element is a [`SpongeElement`], absorbed by addition, so that fields can be used as
+//! [`crate::SpongeAPI::Value`] in the sponges of [`crate::sponge`].
+
+use core::fmt;
+use core::ops::{Add, Mul, Neg, Sub};
+
+use crate::codec::CanonicalValue;
+use crate::sponge::SpongeElement;
+
+mod bls12_381;
+mod goldilocks;
+
+pub use bls12_381::Bls12_381Scalar;
+pub use goldilocks::Goldilocks;
+
+/// An element of a prime field.
+pub trait Field:
+    Copy
+    + Default
+    + Eq
+    + fmt::Debug
+    + CanonicalValue
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Neg<Output = Self>
+{
+    /// The additive identity, which is also the default value
+    const ZERO: Self;
+    /// The multiplicative identity
+    const ONE: Self;
+    /// The modulus of the field, as little-endian 64-bit limbs
+    const MODULUS: &'static [u64];
+    /// The number of bits of the modulus
+    const MODULUS_BITS: u32;
+    /// The smallest generator of the multiplicative group of the field
+    const GENERATOR: Self;
+
+    /// Returns the element `n`, reduced modulo the modulus of the field.
+    fn from_u64(n: u64) -> Self;
+
+    /// Returns the inverse of the element, or `None` if it is zero.
+    fn inverse(&self) -> Option<Self>;
+
+    /// Returns the element `n`, reduced modulo the modulus of the field.
+    fn from_u128(n: u128) -> Self {
+        let shift = Self::from_u64(1 << 32).square();
+        Self::from_u64((n >> 64) as u64) * shift + Self::from_u64(n as u64)
+    }
+
+    /// Returns whether the element is zero.
+    fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+
+    /// Returns the square of the element.
+    fn square(&self) -> Self {
+        *self * *self
+    }
+
+    /// Returns the element raised to the power `exp`, given as little-endian 64-bit limbs.
+    fn pow(&self, exp: &[u64]) -> Self {
+        let mut res = Self::ONE;
+        for limb in exp.iter().rev() {
+            for i in (0..64).rev() {
+                res = res.square();
+                if (limb >> i) & 1 == 1 {
+                    res = res * *self;
+                }
+            }
+        }
+        res
+    }
+
+    /// Returns the element raised to the power `exp`.
+    fn pow_u64(&self, exp: u64) -> Self {
+        self.pow(&[exp])
+    }
+}
+
+// Returns the remainder of the order `p - 1` of the multiplicative group of the field, modulo `m`.
+#[cfg(feature = "alloc")]
+pub(crate) fn group_order_rem<F: Field>(m: u64) -> u64 {
+    // the modulus is odd, so that p - 1 only differs in its lowest limb
+    F::MODULUS
+        .iter()
+        .enumerate()
+        .rev()
+        .fold(0u128, |rem, (i, &limb)| {
+            let limb = if i == 0 { limb - 1 } else { limb };
+            ((rem << 64) | limb as u128) % m as u128
+        }) as u64
+}
+
+// Returns whether `x^alpha` is a permutation of the field, i.e. whether `alpha` is above 1, and coprime with the
+// order `p - 1` of its multiplicative group.
+#[cfg(feature = "alloc")]
+pub(crate) fn is_permutation_exponent<F: Field>(alpha: u64) -> bool {
+    if alpha < 2 {
+        return false;
+    }
+    let (mut a, mut b) = (alpha, group_order_rem::<F>(alpha));
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a == 1
+}
+
+/// Field elements absorb by addition. The tag is spread over as many elements of the capacity as needed for each
+/// of them to hold fewer bits than the modulus, least significant bits first: a single element in large fields,
+/// three in the Goldilocks field.
+impl<F: Field> SpongeElement for F {
+    fn absorb(&mut self, input: F) {
+        *self = *self + input;
+    }
+
+    fn add_tag(capacity: &mut [F], tag: u128) {
+        let bits = (F::MODULUS_BITS - 1).min(128);
+        let elements = 128usize.div_ceil(bits as usize);
+        assert!(
+            capacity.len() >= elements,
+            "the capacity of the sponge must hold at least {elements} elements"
+        );
+        let mut tag = tag;
+        for element in &mut capacity[..elements] {
+            let chunk = if bits == 128 {
+                tag
+            } else {
+                tag & ((1 << bits) - 1)
+            };
+            *element = *element + F::from_u128(chunk);
+            tag = tag.checked_shr(bits).unwrap_or(0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks the field axioms and the consistency of the operations on a few elements
+    #[allow(clippy::eq_op)]
+    pub(super) fn check_field<F: Field>(elements: &[F]) {
+        assert_eq!(F::default(), F::ZERO);
+        assert!(F::ZERO.inverse().is_none());
+        for &a in elements {
+            assert_eq!(a + F::ZERO, a);
+            assert_eq!(a * F::ONE, a);
+            assert_eq!(a - a, F::ZERO);
+            assert_eq!(a + -a, F::ZERO);
+            assert_eq!(a.square(), a * a);
+            assert_eq!(a.pow_u64(5), a * a * a * a * a);
+            assert_eq!(F::from_bytes(&a.to_bytes()).unwrap(), a);
+            if !a.is_zero() {
+                assert_eq!(a * a.inverse().unwrap(), F::ONE);
+            }
+            // Fermat's little theorem
+            assert_eq!(a.pow(F::MODULUS), a);
+            for &b in elements {
+                assert_eq!(a + b, b + a);
+                assert_eq!(a * b, b * a);
+                assert_eq!(a - b, -(b - a));
+                for &c in elements {
+                    assert_eq!((a + b) + c, a + (b + c));
+                    assert_eq!((a * b) * c, a * (b * c));
+                    assert_eq!(a * (b + c), a * b + a * c);
+                }
+            }
+        }
+    }
+
+    // Checks that the generator generates the multiplicative group, whose order has the prime factors `factors`,
+    // and that the smaller elements do not
+    pub(super) fn check_generator<F: Field>(factors: &[u64]) {
+        let generates = |g: F| {
+            factors.iter().all(|&q| {
+                // (p - 1) / q, dividing the limbs from the most significant one
+                let mut exp = F::MODULUS.to_vec();
+                exp[0] -= 1;
+                let mut rem = 0u128;
+                for limb in exp.iter_mut().rev() {
+                    let n = (rem << 64) | *limb as u128;
+                    *limb = (n / q as u128) as u64;
+                    rem = n % q as u128;
+                }
+                assert_eq!(rem, 0, "{q} does not divide the order of the group");
+                g.pow(&exp) != F::ONE
+            })
+        };
+        assert!(generates(F::GENERATOR));
+        let mut g = F::from_u64(2);
+        while g != F::GENERATOR {
+            assert!(!generates(g));
+            g = g + F::ONE;
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn checks_exponents() {
+        // 3 and 5 divide the order of the multiplicative group of the Goldilocks field
+        assert!(!is_permutation_exponent::<Goldilocks>(3));
+        assert!(!is_permutation_exponent::<Goldilocks>(5));
+        assert!(is_permutation_exponent::<Goldilocks>(7));
+        assert!(!is_permutation_exponent::<Bls12_381Scalar>(3));
+        assert!(is_permutation_exponent::<Bls12_381Scalar>(5));
+        assert!(!is_permutation_exponent::<Bls12_381Scalar>(1));
+        assert!(!is_permutation_exponent::<Bls12_381Scalar>(0));
+        assert_eq!(group_order_rem::<Goldilocks>(1 << 32), 0);
+        assert_eq!(
+            group_order_rem::<Goldilocks>(7),
+            (u64::MAX - 0xffff_ffff) % 7
+        );
+    }
+
+    #[test]
+    fn adds_tags() {
+        let tag = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210;
+
+        let mut capacity = [Goldilocks::ONE; 4];
+        Goldilocks::add_tag(&mut capacity, tag);
+        let chunks = [
+            tag & ((1 << 63) - 1),
+            (tag >> 63) & ((1 << 63) - 1),
+            tag >> 126,
+        ];
+        for (element, chunk) in capacity.iter().zip(chunks) {
+            assert_eq!(*element, Goldilocks::ONE + Goldilocks::from_u128(chunk));
+        }
+        assert_eq!(capacity[3], Goldilocks::ONE);
+
+        let mut capacity = [Bls12_381Scalar::ZERO; 2];
+        Bls12_381Scalar::add_tag(&mut capacity, tag);
+        assert_eq!(capacity[0], Bls12_381Scalar::from_u128(tag));
+        assert_eq!(capacity[1], Bls12_381Scalar::ZERO);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least 3 elements")]
+    fn needs_capacity_for_tags() {
+        Goldilocks::add_tag(&mut [Goldilocks::ZERO; 2], 1);
+    }
+}
@@ -0,0 +1,169 @@
+//! This module contains the Grain LFSR, which the Poseidon family of permutations uses as a pseudo-random generator
+//! to derive its round constants and matrices from the parameters of an instance, as the reference implementation
+//! of Poseidon (`generate_parameters_grain.sage`) does.
+
+use crate::field::Field;
+
+// The number of bits of the state of the LFSR
+const STATE_BITS: usize = 80;
+// The number of output bits discarded after initialization
+const WARMUP: usize = 160;
+// The maximal number of limbs of the modulus of a field the LFSR samples
+const MAX_LIMBS: usize = 8;
+
+/// The S-box of the instance, as encoded in the initial state of the LFSR.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrainSBox {
+    /// The S-box `x^alpha`, for a positive `alpha`
+    Power,
+    /// The S-box `x^-1`
+    Inverse,
+}
+
+/// The Grain LFSR, seeded with the parameters of an instance over a prime field.
+#[derive(Clone, Debug)]
+pub struct GrainLfsr {
+    // a ring buffer, where the oldest bit is at `pos`
+    state: [bool; STATE_BITS],
+    pos: usize,
+}
+
+impl GrainLfsr {
+    /// Returns the LFSR of an instance over a prime field of `field_bits` bits, of width `width`,
+    /// with `full_rounds` full rounds and `partial_rounds` partial rounds.
+    pub fn new(
+        field_bits: u32,
+        sbox: GrainSBox,
+        width: usize,
+        full_rounds: usize,
+        partial_rounds: usize,
+    ) -> Self {
+        // the bit widths of the parameters in the initial state, the first of which is the field type (prime)
+        let fields = [
+            (1, 2),
+            (sbox as u64, 4),
+            (field_bits as u64, 12),
+            (width as u64, 12),
+            (full_rounds as u64, 10),
+            (partial_rounds as u64, 10),
+        ];
+        let mut state = [true; STATE_BITS];
+        let mut i = 0;
+        for (value, bits) in fields {
+            assert!(value < 1 << bits, "parameter {value} does not fit the LFSR");
+            for bit in (0..bits).rev() {
+                state[i] = (value >> bit) & 1 == 1;
+                i += 1;
+            }
+        }
+        // the remaining 30 bits are set
+
+        let mut res = GrainLfsr { state, pos: 0 };
+        for _ in 0..WARMUP {
+            res.step();
+        }
+        res
+    }
+
+    // Shifts the register, returning the new bit.
+    fn step(&mut self) -> bool {
+        let bit = |i: usize| self.state[(self.pos + i) % STATE_BITS];
+        let new_bit = bit(62) ^ bit(51) ^ bit(38) ^ bit(23) ^ bit(13) ^ bit(0);
+        self.state[self.pos] = new_bit;
+        self.pos = (self.pos + 1) % STATE_BITS;
+        new_bit
+    }
+
+    /// Returns the next output bit. Output bits are drawn from pairs of LFSR bits: the second bit of a pair is output
+    /// when the first one is set, and the pair is discarded otherwise.
+    pub fn next_bit(&mut self) -> bool {
+        while !self.step() {
+            self.step();
+        }
+        self.step()
+    }
+
+    // Fills `limbs` with the next `bits` output bits, most significant first, as a little-endian integer.
+    fn next_limbs(&mut self, bits: u32, limbs: &mut [u64]) {
+        limbs.fill(0);
+        for i in (0..bits as usize).rev() {
+            if self.next_bit() {
+                limbs[i / 64] |= 1 << (i % 64);
+            }
+        }
+    }
+
+    /// Returns the field element of the next `F::MODULUS_BITS` output bits, drawing new bits until they represent an
+    /// integer below the modulus, as the round constants of Poseidon are sampled.
+    pub fn next_field_element<F: Field>(&mut self) -> F {
+        let mut limbs = [0u64; MAX_LIMBS];
+        let limbs = &mut limbs[..F::MODULUS.len()];
+        loop {
+            self.next_limbs(F::MODULUS_BITS, limbs);
+            // the limbs are compared from the most significant one
+            if limbs.iter().rev().lt(F::MODULUS.iter().rev()) {
+                return from_limbs(limbs);
+            }
+        }
+    }
+
+    /// Returns the field element of the next `F::MODULUS_BITS` output bits, reduced modulo the modulus, as the
+    /// elements of the Cauchy matrices of Poseidon are sampled.
+    pub fn next_field_element_reduced<F: Field>(&mut self) -> F {
+        let mut limbs = [0u64; MAX_LIMBS];
+        let limbs = &mut limbs[..F::MODULUS.len()];
+        self.next_limbs(F::MODULUS_BITS, limbs);
+        from_limbs(limbs)
+    }
+}
+
+// Returns the field element of the little-endian limbs, reduced modulo the modulus.
+fn from_limbs<F: Field>(limbs: &[u64]) -> F {
+    let shift = F::from_u128(1 << 64);
+    limbs
+        .iter()
+        .rev()
+        .fold(F::ZERO, |acc, &limb| acc * shift + F::from_u64(limb))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bls12_381Scalar, Goldilocks};
+    use std::prelude::rust_2021::*;
+
+    // These values are computed by an independent implementation of `generate_parameters_grain.sage`
+    #[test]
+    fn samples() {
+        let mut lfsr = GrainLfsr::new(255, GrainSBox::Power, 3, 8, 57);
+        assert_eq!(
+            lfsr.next_field_element::<Bls12_381Scalar>().to_limbs(),
+            [
+                0x9540_d1f2_5091_0880,
+                0xff4a_2c3b_c81d_42e0,
+                0xf749_05cc_7dae_4ca9,
+                0x6c4f_fa72_3eaf_1a7b,
+            ]
+        );
+
+        let mut lfsr = GrainLfsr::new(64, GrainSBox::Power, 12, 8, 22);
+        let bits: Vec<bool> = (0..8).map(|_| lfsr.next_bit()).collect();
+        assert_eq!(bits, [false, false, false, true, false, false, true, true]);
+        assert_eq!(
+            lfsr.next_field_element::<Goldilocks>().to_u64(),
+            0xdcf3_3aba_214f_4630
+        );
+
+        // the S-box is part of the seed
+        let mut lfsr = GrainLfsr::new(255, GrainSBox::Inverse, 3, 8, 57);
+        assert_eq!(
+            lfsr.next_field_element::<Bls12_381Scalar>().to_limbs(),
+            [
+                0x6b05_afd6_f0ae_0af4,
+                0x0114_5e02_dadf_8c01,
+                0x58a5_b0f5_1ebf_2af0,
+                0x4645_8ff6_03a3_ceca,
+            ]
+        );
+    }
+}
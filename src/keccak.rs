@@ -0,0 +1,318 @@
+//! This module contains an implementation of the Keccak-f\[1600\] permutation, and of the SHA3-256 hash function and
+//! SHAKE256 extendable-output function built on top of it, as specified in FIPS 202.
+//! SHA3-256 is what the SAFE spec uses to compute the tag of a sponge from its IOPattern, see [`crate::sponge`], and
+//! SHAKE256 is what Rescue-Prime derives its round constants from, see [`crate::rescue`].
+//! The permutation is also available as a [`Permutation`] over 64-bit lanes, to build sponges on.
+
+use hybrid_array::Array;
+use typenum::U25;
+
+use crate::sponge::Permutation;
+
+// The round constants of the iota step
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000_0000_0000_0001,
+    0x0000_0000_0000_8082,
+    0x8000_0000_0000_808a,
+    0x8000_0000_8000_8000,
+    0x0000_0000_0000_808b,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8009,
+    0x0000_0000_0000_008a,
+    0x0000_0000_0000_0088,
+    0x0000_0000_8000_8009,
+    0x0000_0000_8000_000a,
+    0x0000_0000_8000_808b,
+    0x8000_0000_0000_008b,
+    0x8000_0000_0000_8089,
+    0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002,
+    0x8000_0000_0000_0080,
+    0x0000_0000_0000_800a,
+    0x8000_0000_8000_000a,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8080,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8008,
+];
+
+// The rho and pi steps move the lanes along a single cycle, which visits these positions (x + 5 * y) in order,
+// rotating each lane by the matching offset
+const PI_CYCLE: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+const RHO_OFFSETS: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// Applies the Keccak-f\[1600\] permutation to the state, whose lane at position `(x, y)` is `state[x + 5 * y]`.
+pub fn keccak_f1600(state: &mut [u64; 25]) {
+    for round_constant in ROUND_CONSTANTS {
+        // theta
+        let mut columns = [0u64; 5];
+        for (i, lane) in state.iter().enumerate() {
+            columns[i % 5] ^= lane;
+        }
+        for (i, lane) in state.iter_mut().enumerate() {
+            let x = i % 5;
+            *lane ^= columns[(x + 4) % 5] ^ columns[(x + 1) % 5].rotate_left(1);
+        }
+
+        // rho and pi
+        let mut moved = state[1];
+        for (&position, &offset) in PI_CYCLE.iter().zip(&RHO_OFFSETS) {
+            let next = state[position];
+            state[position] = moved.rotate_left(offset);
+            moved = next;
+        }
+
+        // chi
+        for row in state.chunks_exact_mut(5) {
+            let lanes = [row[0], row[1], row[2], row[3], row[4]];
+            for (x, lane) in row.iter_mut().enumerate() {
+                *lane = lanes[x] ^ (!lanes[(x + 1) % 5] & lanes[(x + 2) % 5]);
+            }
+        }
+
+        // iota
+        state[0] ^= round_constant;
+    }
+}
+
+/// The Keccak-f\[1600\] permutation, over a state of 25 lanes, see [`keccak_f1600`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeccakF1600;
+
+impl Permutation for KeccakF1600 {
+    type Element = u64;
+    type Width = U25;
+
+    fn permute(&self, state: &mut Array<u64, U25>) {
+        keccak_f1600(&mut state.0);
+    }
+}
+
+// The number of bytes absorbed by SHA3-256 and SHAKE256 between two permutations
+const RATE: usize = 136;
+
+// A sponge over the bytes of the rate of the Keccak-f[1600] state, as the hash functions of FIPS 202 use it
+#[derive(Clone, Debug)]
+struct ByteSponge {
+    state: [u64; 25],
+    // the position in the rate at which the next byte is absorbed or squeezed
+    pos: usize,
+}
+
+impl ByteSponge {
+    fn new() -> Self {
+        ByteSponge {
+            state: [0; 25],
+            pos: 0,
+        }
+    }
+
+    // Bytes are XORed into the lanes in little-endian order
+    fn xor_byte(&mut self, pos: usize, byte: u8) {
+        self.state[pos / 8] ^= (byte as u64) << (8 * (pos % 8));
+    }
+
+    fn absorb(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.xor_byte(self.pos, byte);
+            self.pos += 1;
+            if self.pos == RATE {
+                keccak_f1600(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    // Absorbs the domain separation bits of the function, followed by the first and last bits of the pad10*1
+    // padding, and permutes the state before squeezing.
+    fn pad(&mut self, domain_separation: u8) {
+        self.xor_byte(self.pos, domain_separation);
+        self.xor_byte(RATE - 1, 0x80);
+        keccak_f1600(&mut self.state);
+        self.pos = 0;
+    }
+
+    fn squeeze(&mut self, out: &mut [u8]) {
+        for byte in out {
+            if self.pos == RATE {
+                keccak_f1600(&mut self.state);
+                self.pos = 0;
+            }
+            *byte = (self.state[self.pos / 8] >> (8 * (self.pos % 8))) as u8;
+            self.pos += 1;
+        }
+    }
+}
+
+/// The SHA3-256 hash function, which hashes the bytes passed to `update` in order.
+#[derive(Clone, Debug)]
+pub struct Sha3_256(ByteSponge);
+
+impl Default for Sha3_256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sha3_256 {
+    /// Returns a hasher which has not absorbed any byte.
+    pub fn new() -> Self {
+        Sha3_256(ByteSponge::new())
+    }
+
+    /// Absorbs `bytes` in the hasher.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.absorb(bytes);
+    }
+
+    /// Returns the hash of the absorbed bytes.
+    pub fn finalize(mut self) -> [u8; 32] {
+        self.0.pad(0x06);
+        let mut res = [0u8; 32];
+        self.0.squeeze(&mut res);
+        res
+    }
+}
+
+/// The SHAKE256 extendable-output function, which hashes the bytes passed to `update` in order, and outputs as many
+/// bytes as needed from the [`Shake256Reader`] returned by `finalize`.
+#[derive(Clone, Debug)]
+pub struct Shake256(ByteSponge);
+
+impl Default for Shake256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Shake256 {
+    /// Returns a hasher which has not absorbed any byte.
+    pub fn new() -> Self {
+        Shake256(ByteSponge::new())
+    }
+
+    /// Absorbs `bytes` in the hasher.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.0.absorb(bytes);
+    }
+
+    /// Returns the reader of the output of the function on the absorbed bytes.
+    pub fn finalize(mut self) -> Shake256Reader {
+        self.0.pad(0x1f);
+        Shake256Reader(self.0)
+    }
+}
+
+/// The output of SHAKE256, read in order.
+#[derive(Clone, Debug)]
+pub struct Shake256Reader(ByteSponge);
+
+impl Shake256Reader {
+    /// Fills `out` with the next bytes of the output.
+    pub fn read(&mut self, out: &mut [u8]) {
+        self.0.squeeze(out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+    use std::prelude::rust_2021::*;
+    use std::vec;
+
+    fn sha3_256(bytes: &[u8]) -> [u8; 32] {
+        let mut hasher = Sha3_256::new();
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+
+    fn shake256(bytes: &[u8], len: usize) -> Vec<u8> {
+        let mut hasher = Shake256::new();
+        hasher.update(bytes);
+        let mut res = vec![0; len];
+        hasher.finalize().read(&mut res);
+        res
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().fold(String::new(), |mut res, b| {
+            write!(res, "{b:02x}").unwrap();
+            res
+        })
+    }
+
+    #[test]
+    fn permutes() {
+        // the first lanes of Keccak-f[1600] applied to the zero state, as in the Keccak team's test vectors
+        let mut state = [0u64; 25];
+        keccak_f1600(&mut state);
+        assert_eq!(state[0], 0xf1258f7940e1dde7);
+        assert_eq!(state[1], 0x84d5ccf933c0478a);
+        assert_eq!(state[24], 0xeaf1ff7b5ceca249);
+    }
+
+    #[test]
+    fn hashes() {
+        assert_eq!(
+            hex(&sha3_256(b"")),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            hex(&sha3_256(b"abc")),
+            "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532"
+        );
+        // over several blocks, and fed in pieces
+        let bytes: Vec<u8> = (0..=255).collect();
+        let mut hasher = Sha3_256::new();
+        for chunk in bytes.chunks(100) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), sha3_256(&bytes));
+        assert_eq!(
+            hex(&sha3_256(&bytes)),
+            "9b04c091da96b997afb8f2585d608aebe9c4a904f7d52c8f28c7e4d2dd9fba5f"
+        );
+        // a message which fills the rate exactly
+        assert_eq!(
+            hex(&sha3_256(&[0x61; RATE])),
+            "3fc5559f14db8e453a0a3091edbd2bc25e11528d81c66fa570a4efdcc2695ee1"
+        );
+    }
+
+    #[test]
+    fn extends_output() {
+        assert_eq!(
+            hex(&shake256(b"", 32)),
+            "46b9dd2b0ba88d13233b3feb743eeb243fcd52ea62b81b82b50c27646ed5762f"
+        );
+        assert_eq!(
+            hex(&shake256(b"abc", 32)),
+            "483366601360a8771c6863080cc4114d8db44530f8f1e1ee4f94ea37e78b5739"
+        );
+        assert_eq!(
+            hex(&shake256(&[0x61; RATE], 32)),
+            "8fcc5a08f0a1f6827c9cf64ee8d16e0443106359ca6c8efd230759256f44996a"
+        );
+        // the output spans several permutations, and is the same whichever way it is read
+        let bytes: Vec<u8> = (0..=255).collect();
+        let output = shake256(&bytes, 300);
+        assert_eq!(
+            hex(&output[268..]),
+            "3c70ae13f5cb71da25a3d71e9379dbec8795b9d1d60d3a5c084d879648faf507"
+        );
+        let mut hasher = Shake256::new();
+        hasher.update(&bytes);
+        let mut reader = hasher.finalize();
+        let mut pieces = vec![0; 300];
+        for chunk in pieces.chunks_mut(7) {
+            reader.read(chunk);
+        }
+        assert_eq!(pieces, output);
+    }
+}
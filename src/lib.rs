@@ -1,3 +1,4 @@
+#![no_std]
 #![warn(
     missing_debug_implementations,
     missing_docs,
@@ -13,13 +14,47 @@
 //!
 //! [1]: https://hackmd.io/bHgsH6mMStCVibM_wYvb2w#SAFE-Sponge-API-for-Field-Elements-%E2%80%93-A-Toolbox-for-ZK-Hash-Applications
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
+
+pub mod codec;
+pub mod field;
+pub mod grain;
+pub mod keccak;
+pub mod owned;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod parse;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod pattern;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod poseidon;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod poseidon2;
+#[cfg(feature = "alloc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+pub mod rescue;
+pub mod sponge;
 pub mod traits;
+pub mod tuples;
 
-use std::iter;
-use std::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
 
+use codec::{BytePacking, Packed, PackedLength};
 use hybrid_array::{Array, ArraySize};
-use traits::{Absorb, Cons, Consume, IOWord, List, Nil, Norm, Normalize, Squeeze, Use};
+use traits::{
+    Absorb, Concat, Cons, Consume, IOWord, List, Nil, Norm, Normalize, PatternDisplay, Squeeze, Use,
+};
+use tuples::{ArrayTuple, ArrayTupleMut};
 use typenum::Unsigned;
 
 /// The Error returned at runtime by the sponge API in case the finalize operation fails.
@@ -27,8 +62,59 @@ use typenum::Unsigned;
 pub enum Error {
     /// Error returned when the sponge is not in a state where it can be finalized.
     ParameterUsageMismatch,
+    /// Error returned when decoding bytes which are not the canonical encoding of a value.
+    NonCanonicalEncoding,
+    /// Error returned when the number of elements provided differs from the one the operation expects.
+    LengthMismatch {
+        /// The number of elements the operation expects
+        expected: usize,
+        /// The number of elements provided
+        actual: usize,
+    },
+    /// Error returned when merging successive words of an IO pattern counts more than `u32::MAX` elements.
+    WordOverflow,
+    /// Error returned when an operation is not allowed by the head of an IO pattern.
+    UnexpectedOperation {
+        /// The head of the pattern, if any
+        expected: Option<SpongeOp>,
+        /// The operation attempted
+        actual: SpongeOp,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ParameterUsageMismatch => {
+                f.write_str("the sponge was not used according to its IO pattern")
+            }
+            Error::NonCanonicalEncoding => {
+                f.write_str("the bytes are not the canonical encoding of a value")
+            }
+            Error::WordOverflow => {
+                f.write_str("the merged words of the pattern count more than u32::MAX elements")
+            }
+            Error::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} elements, got {actual}")
+            }
+            Error::UnexpectedOperation {
+                expected: Some(expected),
+                actual,
+            } => write!(f, "unexpected operation {actual}, expected {expected}"),
+            Error::UnexpectedOperation {
+                expected: None,
+                actual,
+            } => write!(
+                f,
+                "unexpected operation {actual}, expected the end of the pattern"
+            ),
+        }
+    }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 /// The SpongeWord type is lifted straight from the Neptune codebase.
 /// See `<https://github.com/filecoin-project/neptune/blob/master/src/sponge/api.rs>`
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -39,54 +125,426 @@ pub enum SpongeOp {
     Squeeze(u32),
 }
 
+/// Sponge operations are displayed as e.g. `Absorb(5)`, and can be parsed back, see [`parse`].
+impl fmt::Display for SpongeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpongeOp::Absorb(n) => write!(f, "Absorb({n})"),
+            SpongeOp::Squeeze(n) => write!(f, "Squeeze({n})"),
+        }
+    }
+}
+
 /// Conversion from a type-level IOWord to a crate::SpongeOp
 /// This is, morally speaking, an extension trait of the IOWord trait,
 /// though Rust can of course not check exhaustivity.
 pub trait ToSpongeOp: IOWord {
+    /// The term-level representation of the type-level operation
+    const SPONGE_OP: SpongeOp;
+
     /// Converts the type-level operation to its term-level representation
-    fn to_sponge_op() -> SpongeOp;
+    fn to_sponge_op() -> SpongeOp {
+        Self::SPONGE_OP
+    }
 }
 
 impl<U: Unsigned> ToSpongeOp for Absorb<U> {
-    fn to_sponge_op() -> SpongeOp {
-        SpongeOp::Absorb(U::to_u32())
-    }
+    const SPONGE_OP: SpongeOp = SpongeOp::Absorb(U::U32);
 }
 
 impl<U: Unsigned> ToSpongeOp for Squeeze<U> {
-    fn to_sponge_op() -> SpongeOp {
-        SpongeOp::Squeeze(U::to_u32())
-    }
+    const SPONGE_OP: SpongeOp = SpongeOp::Squeeze(U::U32);
 }
 
 /// The type describing the I/O pattern of a sponge, at a term level.
-#[derive(Clone, Debug, PartialEq)]
-pub struct IOPattern(pub Vec<SpongeOp>);
+/// A pattern either owns its operations, or borrows them from static data: the patterns computed from
+/// type-level IOPatterns (see [`ToIOPattern::OPS`]) and those built with [`IOPattern::from_static`] do not allocate,
+/// and are the only ones available without the `alloc` feature.
+#[derive(Clone)]
+pub struct IOPattern(Ops);
+
+#[derive(Clone)]
+enum Ops {
+    Static(StaticOps),
+    Slice(&'static [SpongeOp]),
+    #[cfg(feature = "alloc")]
+    Owned(Vec<SpongeOp>),
+}
+
+impl IOPattern {
+    /// Returns the pattern of the operations `ops`.
+    #[cfg(feature = "alloc")]
+    pub fn new(ops: Vec<SpongeOp>) -> Self {
+        IOPattern(Ops::Owned(ops))
+    }
+
+    /// Returns the pattern of the operations `ops`, without copying them.
+    pub const fn from_static(ops: &'static [SpongeOp]) -> Self {
+        IOPattern(Ops::Slice(ops))
+    }
+
+    /// Returns the number of words in the pattern.
+    pub fn len(&self) -> usize {
+        match &self.0 {
+            Ops::Static(ops) => ops.len(),
+            Ops::Slice(ops) => ops.len(),
+            #[cfg(feature = "alloc")]
+            Ops::Owned(ops) => ops.len(),
+        }
+    }
+
+    /// Returns whether the pattern has no word.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the first word of the pattern, if any.
+    pub fn first(&self) -> Option<SpongeOp> {
+        self.iter().next()
+    }
+
+    /// Returns an iterator over the words of the pattern.
+    pub fn iter(&self) -> Iter<'_> {
+        match &self.0 {
+            Ops::Static(ops) => Iter(IterRepr::Static(StaticIter::new(*ops))),
+            Ops::Slice(ops) => Iter(IterRepr::Slice(ops.iter())),
+            #[cfg(feature = "alloc")]
+            Ops::Owned(ops) => Iter(IterRepr::Slice(ops.iter())),
+        }
+    }
+
+    /// Returns the words of the pattern in a vector.
+    #[cfg(feature = "alloc")]
+    pub fn to_vec(&self) -> Vec<SpongeOp> {
+        self.iter().collect()
+    }
+}
+
+impl PartialEq for IOPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+// Patterns are debugged as the list of their words, whatever their representation.
+impl fmt::Debug for IOPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("IOPattern")
+            .field(&OpsDebug(self.iter()))
+            .finish()
+    }
+}
+
+struct OpsDebug<'a>(Iter<'a>);
+
+impl fmt::Debug for OpsDebug<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.clone()).finish()
+    }
+}
+
+/// Patterns are displayed as comma-separated operations, e.g. `Absorb(5), Squeeze(3)`, and can be parsed back,
+/// see [`parse`].
+impl fmt::Display for IOPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            fmt::Display::fmt(&op, f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl From<Vec<SpongeOp>> for IOPattern {
+    fn from(ops: Vec<SpongeOp>) -> Self {
+        IOPattern::new(ops)
+    }
+}
+
+impl From<StaticOps> for IOPattern {
+    fn from(ops: StaticOps) -> Self {
+        IOPattern(Ops::Static(ops))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FromIterator<SpongeOp> for IOPattern {
+    fn from_iter<T: IntoIterator<Item = SpongeOp>>(iter: T) -> Self {
+        IOPattern::new(iter.into_iter().collect())
+    }
+}
+
+impl<'a> IntoIterator for &'a IOPattern {
+    type Item = SpongeOp;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for IOPattern {
+    type Item = SpongeOp;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        match self.0 {
+            Ops::Static(ops) => IntoIter(IntoIterRepr::Borrowed(Iter(IterRepr::Static(
+                StaticIter::new(ops),
+            )))),
+            Ops::Slice(ops) => IntoIter(IntoIterRepr::Borrowed(Iter(IterRepr::Slice(ops.iter())))),
+            #[cfg(feature = "alloc")]
+            Ops::Owned(ops) => IntoIter(IntoIterRepr::Owned(ops.into_iter())),
+        }
+    }
+}
+
+/// An iterator over the words of an [`IOPattern`] it owns, which does not allocate when the pattern borrows
+/// static data.
+#[derive(Clone, Debug)]
+pub struct IntoIter(IntoIterRepr);
+
+#[derive(Clone, Debug)]
+enum IntoIterRepr {
+    Borrowed(Iter<'static>),
+    #[cfg(feature = "alloc")]
+    Owned(alloc::vec::IntoIter<SpongeOp>),
+}
+
+impl Iterator for IntoIter {
+    type Item = SpongeOp;
+
+    fn next(&mut self) -> Option<SpongeOp> {
+        match &mut self.0 {
+            IntoIterRepr::Borrowed(ops) => ops.next(),
+            #[cfg(feature = "alloc")]
+            IntoIterRepr::Owned(ops) => ops.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            IntoIterRepr::Borrowed(ops) => ops.size_hint(),
+            #[cfg(feature = "alloc")]
+            IntoIterRepr::Owned(ops) => ops.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+/// An iterator over the words of an [`IOPattern`], see [`IOPattern::iter`].
+#[derive(Clone, Debug)]
+pub struct Iter<'a>(IterRepr<'a>);
+
+#[derive(Clone, Debug)]
+enum IterRepr<'a> {
+    Static(StaticIter),
+    Slice(core::slice::Iter<'a, SpongeOp>),
+}
+
+// An iterator over a tree of static operations, which walks down linked lists, and the left side of concatenations.
+// Once the words of a left side are exhausted, it finds its way to the next word by descending from the root,
+// which costs the height of the tree, rather than keeping a stack of the right sides left to visit.
+#[derive(Clone, Debug)]
+struct StaticIter {
+    root: StaticOps,
+    // a node whose words start with the next word of the iteration
+    cursor: StaticOps,
+    pos: usize,
+}
+
+impl StaticIter {
+    fn new(root: StaticOps) -> Self {
+        StaticIter {
+            root,
+            cursor: root,
+            pos: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<SpongeOp> {
+        loop {
+            match self.cursor.node {
+                Node::Cons(op, tail) => {
+                    self.cursor = *tail;
+                    self.pos += 1;
+                    return Some(op);
+                }
+                Node::Concat(left, right) => {
+                    self.cursor = if left.is_empty() { *right } else { *left };
+                }
+                Node::Nil if self.pos < self.root.len => self.cursor = self.seek(),
+                Node::Nil => return None,
+            }
+        }
+    }
+
+    // the linked list starting with the word at `pos`
+    fn seek(&self) -> StaticOps {
+        let mut node = self.root;
+        let mut i = self.pos;
+        loop {
+            match node.node {
+                Node::Concat(left, _) if i < left.len => node = *left,
+                Node::Concat(left, right) => {
+                    i -= left.len;
+                    node = *right;
+                }
+                Node::Cons(_, _) if i == 0 => return node,
+                Node::Cons(_, tail) => {
+                    i -= 1;
+                    node = *tail;
+                }
+                Node::Nil => unreachable!("the position is within the length of the tree"),
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.root.len - self.pos
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = SpongeOp;
+
+    fn next(&mut self) -> Option<SpongeOp> {
+        match &mut self.0 {
+            IterRepr::Static(ops) => ops.next(),
+            IterRepr::Slice(ops) => ops.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match &self.0 {
+            IterRepr::Static(ops) => ops.len(),
+            IterRepr::Slice(ops) => ops.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+/// A list of sponge operations stored in static memory, which type-level IOPatterns compute at compile time,
+/// see [`ToIOPattern::OPS`].
+/// This is a linked list rather than an array, as constants of a generic type cannot be borrowed for the
+/// `'static` lifetime: each node borrows the (concrete) list of the tail of the pattern instead.
+/// As type-level patterns, the list may also be the concatenation of two lists, see [`traits::Concat`].
+#[derive(Clone, Copy)]
+pub struct StaticOps {
+    node: Node,
+    len: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Node {
+    Nil,
+    Cons(SpongeOp, &'static StaticOps),
+    Concat(&'static StaticOps, &'static StaticOps),
+}
+
+impl StaticOps {
+    /// The empty list.
+    pub const EMPTY: StaticOps = StaticOps {
+        node: Node::Nil,
+        len: 0,
+    };
+
+    /// Returns the list of `op` followed by the operations of `tail`.
+    pub const fn cons(op: SpongeOp, tail: &'static StaticOps) -> StaticOps {
+        StaticOps {
+            node: Node::Cons(op, tail),
+            len: tail.len + 1,
+        }
+    }
+
+    /// Returns the list of the operations of `left` followed by those of `right`.
+    pub const fn concat(left: &'static StaticOps, right: &'static StaticOps) -> StaticOps {
+        StaticOps {
+            node: Node::Concat(left, right),
+            len: left.len + right.len,
+        }
+    }
+
+    /// Returns the number of operations in the list.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the list has no operation.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl fmt::Debug for StaticOps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(Iter(IterRepr::Static(StaticIter::new(*self))))
+            .finish()
+    }
+}
 
 // TODO : convert SpongeOp -> IOWord using macros
 
 /// Conversion from a trait::List type-level IOPattern to a crate::IOpattern
 /// This is morally an extension trait of the List trait, though Rust can of
 /// course not check exhaustivity.
+/// The constants describe the pattern without converting it.
 pub trait ToIOPattern {
-    /// Converts the type-level pattern to its term-level representation
-    fn to_iopattern() -> IOPattern;
-}
+    /// The number of words in the pattern
+    const LEN: usize;
+    /// The first word of the pattern, if any
+    const NEXT_OP: Option<SpongeOp>;
+    /// The total number of elements absorbed over the pattern
+    const TOTAL_ABSORB: u64;
+    /// The total number of elements squeezed over the pattern
+    const TOTAL_SQUEEZE: u64;
+    /// The words of the pattern, computed at compile time
+    const OPS: StaticOps;
 
-impl ToIOPattern for Nil {
+    /// Converts the type-level pattern to its term-level representation, which borrows `OPS` and does not allocate
     fn to_iopattern() -> IOPattern {
-        IOPattern(vec![])
+        IOPattern::from(Self::OPS)
     }
 }
+
+impl ToIOPattern for Nil {
+    const LEN: usize = 0;
+    const NEXT_OP: Option<SpongeOp> = None;
+    const TOTAL_ABSORB: u64 = 0;
+    const TOTAL_SQUEEZE: u64 = 0;
+    const OPS: StaticOps = StaticOps::EMPTY;
+}
+
 impl<Item: ToSpongeOp, T: List + ToIOPattern> ToIOPattern for Cons<Item, T> {
-    fn to_iopattern() -> IOPattern {
-        // TODO: avoid the quadratic cost of prepending here
-        IOPattern(
-            iter::once(<Item as ToSpongeOp>::to_sponge_op())
-                .chain(T::to_iopattern().0)
-                .collect(),
-        )
-    }
+    const LEN: usize = 1 + T::LEN;
+    const NEXT_OP: Option<SpongeOp> = Some(Item::SPONGE_OP);
+    const TOTAL_ABSORB: u64 = match Item::SPONGE_OP {
+        SpongeOp::Absorb(n) => n as u64 + T::TOTAL_ABSORB,
+        SpongeOp::Squeeze(_) => T::TOTAL_ABSORB,
+    };
+    const TOTAL_SQUEEZE: u64 = match Item::SPONGE_OP {
+        SpongeOp::Absorb(_) => T::TOTAL_SQUEEZE,
+        SpongeOp::Squeeze(n) => n as u64 + T::TOTAL_SQUEEZE,
+    };
+    const OPS: StaticOps = StaticOps::cons(Item::SPONGE_OP, &T::OPS);
+}
+
+impl<L: List + ToIOPattern, R: List + ToIOPattern> ToIOPattern for Concat<L, R> {
+    const LEN: usize = L::LEN + R::LEN;
+    const NEXT_OP: Option<SpongeOp> = match L::NEXT_OP {
+        Some(op) => Some(op),
+        None => R::NEXT_OP,
+    };
+    const TOTAL_ABSORB: u64 = L::TOTAL_ABSORB + R::TOTAL_ABSORB;
+    const TOTAL_SQUEEZE: u64 = L::TOTAL_SQUEEZE + R::TOTAL_SQUEEZE;
+    const OPS: StaticOps = StaticOps::concat(&L::OPS, &R::OPS);
 }
 
 /// This is the SpongeAPI trait as you can find it in Neptune,
@@ -112,6 +570,38 @@ pub trait SpongeAPI {
     // This differs from the original API in that it takes a mutable slice instead of returning a Vec.
     fn squeeze(&mut self, length: u32, elements: &mut [Self::Value], acc: &mut Self::Acc);
 
+    /// This injects the elements of several arrays, one after the other, as a single IO pattern word.
+    /// The default implementation calls `absorb` on each of the non-empty arrays in turn, which the SpongeAPI allows
+    /// since successive absorb calls aggregate. Implementations may override it, e.g. to process all the
+    /// elements in a single call.
+    fn absorb_parts(&mut self, parts: &[&[Self::Value]], acc: &mut Self::Acc) {
+        for part in parts.iter().filter(|part| !part.is_empty()) {
+            let length =
+                u32::try_from(part.len()).expect("a part holds more than u32::MAX elements");
+            self.absorb(length, part, acc);
+        }
+    }
+
+    /// This extracts elements into several arrays, one after the other, as a single IO pattern word.
+    /// The default implementation calls `squeeze` on each of the non-empty arrays in turn, which the SpongeAPI allows
+    /// since successive squeeze calls aggregate. Implementations may override it, e.g. to process all the
+    /// elements in a single call.
+    fn squeeze_parts(&mut self, parts: &mut [&mut [Self::Value]], acc: &mut Self::Acc) {
+        for part in parts.iter_mut().filter(|part| !part.is_empty()) {
+            let length =
+                u32::try_from(part.len()).expect("a part holds more than u32::MAX elements");
+            self.squeeze(length, part, acc);
+        }
+    }
+
+    /// This extracts `U` elements from the state into a new array, as a single IO pattern word.
+    /// The array is built by the implementation, so that values need not have a default to fill it before the squeeze.
+    /// Implementations whose values have a default can delegate to [`squeeze_array_by_default`].
+    fn squeeze_array<U>(&mut self, acc: &mut Self::Acc) -> Array<Self::Value, U>
+    where
+        Self: Sized,
+        U: ArraySize<Self::Value>;
+
     /// This marks the end of the sponge life, preventing any further operation.
     /// In particular, the state is erased from memory. The result is OK, or an error
     // This differs from the original API in that if does not take a final Self::Acc argument.
@@ -120,14 +610,78 @@ pub trait SpongeAPI {
     fn finish(&mut self) -> Result<(), Error>;
 }
 
+/// This is a simplified version of the SpongeAPI trait, for sponges which do not need an accumulator
+/// (e.g. native hashing, as opposed to in-circuit hashing).
+/// Any implementation of this trait is a SpongeAPI with a unit accumulator.
+pub trait NativeSpongeAPI {
+    /// The type of the elements froming the I/O of the sponge
+    type Value;
+
+    /// This initializes the internal state of the sponge, see `SpongeAPI::start`.
+    fn start(&mut self, p: IOPattern, domain_separator: Option<u32>);
+
+    /// This injects `length` field elements to the state from the array `elements`, see `SpongeAPI::absorb`.
+    fn absorb(&mut self, length: u32, elements: &[Self::Value]);
+
+    /// This extracts `length` field elements from the state to the array `elements`, see `SpongeAPI::squeeze`.
+    fn squeeze(&mut self, length: u32, elements: &mut [Self::Value]);
+
+    /// This extracts `U` elements from the state into a new array, see `SpongeAPI::squeeze_array`.
+    /// Implementations whose values have a default can delegate to [`squeeze_array_by_default`].
+    fn squeeze_array<U>(&mut self) -> Array<Self::Value, U>
+    where
+        Self: Sized,
+        U: ArraySize<Self::Value>;
+
+    /// This marks the end of the sponge life, see `SpongeAPI::finish`.
+    fn finish(&mut self) -> Result<(), Error>;
+}
+
+impl<S: NativeSpongeAPI> SpongeAPI for S {
+    type Acc = ();
+    type Value = S::Value;
+
+    fn start(&mut self, p: IOPattern, domain_separator: Option<u32>, _acc: &mut ()) {
+        NativeSpongeAPI::start(self, p, domain_separator)
+    }
+
+    fn absorb(&mut self, length: u32, elements: &[Self::Value], _acc: &mut ()) {
+        NativeSpongeAPI::absorb(self, length, elements)
+    }
+
+    fn squeeze(&mut self, length: u32, elements: &mut [Self::Value], _acc: &mut ()) {
+        NativeSpongeAPI::squeeze(self, length, elements)
+    }
+
+    fn squeeze_array<U>(&mut self, _acc: &mut ()) -> Array<Self::Value, U>
+    where
+        U: ArraySize<Self::Value>,
+    {
+        NativeSpongeAPI::squeeze_array(self)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        NativeSpongeAPI::finish(self)
+    }
+}
+
 /// This is a slightly extended generic NewType wrapper around the original SpongeAPI.
 /// It is decorated with the IOPattern I intended for this sponge instance.
-#[derive(Debug)]
 pub struct ExtraSponge<A: SpongeAPI, I: List> {
     api: A,
     _current_pattern: PhantomData<I>,
 }
 
+// The pattern is displayed in its term-level form rather than as a PhantomData of a type-level list.
+impl<A: SpongeAPI + fmt::Debug, I: List> fmt::Debug for ExtraSponge<A, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtraSponge")
+            .field("api", &self.api)
+            .field("pattern", &PatternDisplay::<I>::new())
+            .finish()
+    }
+}
+
 impl<A: SpongeAPI, I: List> ExtraSponge<A, I> {
     // This is the internal constructor for the ExtraSponge type: a simple wrapper, which needs type annotations
     // to be used properly. This should remain private.
@@ -145,14 +699,60 @@ impl<A: SpongeAPI, I: List> ExtraSponge<A, I> {
         // Mandated by the existence of a Drop implementation which we cannot move out of.
         // Safe since the only type that differs between source and destination is a Phantom
         let res =
-            unsafe { std::mem::transmute_copy::<ExtraSponge<A, I>, ExtraSponge<A, J>>(&self) };
+            unsafe { core::mem::transmute_copy::<ExtraSponge<A, I>, ExtraSponge<A, J>>(&self) };
         // This is really important, as it lets us bypass the drop logic, which would blow up in a
         // non-empty Sponge.
-        std::mem::forget(self);
+        core::mem::forget(self);
+        res
+    }
+
+    // This finishes and drops the underlying sponge, whatever the remaining pattern, and returns the result of its
+    // `finish`. This bypasses the drop logic, and should only be used on sponges which cannot be used any further.
+    fn finish_unchecked(self) -> Result<(), Error> {
+        let mut sponge = ManuallyDrop::new(self);
+        let res = sponge.api.finish();
+        // Safety: the sponge is not used after its api is dropped, and its own drop never runs
+        unsafe { core::ptr::drop_in_place(&mut sponge.api) };
         res
     }
 }
 
+impl<A: SpongeAPI> ExtraSponge<A, Nil> {
+    // This finishes the underlying sponge, returning the error of its `finish` where the drop would panic on it.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        self.finish_unchecked()
+    }
+}
+
+/// These functions describe the remaining IOPattern of the sponge, as a way to report where a protocol stopped.
+/// They are computed at compile time from the pattern `I`.
+impl<A: SpongeAPI, I: List + ToIOPattern> ExtraSponge<A, I> {
+    /// Returns the remaining IOPattern of the sponge.
+    pub fn remaining(&self) -> IOPattern {
+        I::to_iopattern()
+    }
+
+    /// Returns the next operation expected by the sponge, if any.
+    pub fn next_op(&self) -> Option<SpongeOp> {
+        I::NEXT_OP
+    }
+
+    /// Returns the total number of elements which remain to be absorbed by the sponge.
+    pub fn remaining_absorb(&self) -> u64 {
+        I::TOTAL_ABSORB
+    }
+
+    /// Returns the total number of elements which remain to be squeezed from the sponge.
+    pub fn remaining_squeeze(&self) -> u64 {
+        I::TOTAL_SQUEEZE
+    }
+
+    /// Returns whether the IOPattern of the sponge has been fully used.
+    pub fn is_complete(&self) -> bool {
+        I::LEN == 0
+    }
+}
+
 impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I>
 where
     Norm<I>: ToIOPattern, // Satisfied in all cases
@@ -193,8 +793,147 @@ impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I> {
         self.api.absorb(U::to_u32(), harray.as_slice(), acc);
         self.repattern()
     }
+
+    /// This function is used to absorb elements in the sponge from a borrowed array, see `absorb`.
+    pub fn absorb_ref<U>(
+        mut self,
+        harray: &Array<A::Value, U>,
+        acc: &mut A::Acc,
+    ) -> ExtraSponge<A, Use<I, Absorb<U>>>
+    where
+        U: ArraySize<A::Value>,
+        I: Consume<Absorb<U>>,
+    {
+        self.api.absorb(U::to_u32(), harray.as_slice(), acc);
+        self.repattern()
+    }
+
+    /// This function is used to absorb elements in the sponge from a slice, whose length is checked at runtime
+    /// to be `U`. If it is not, this returns the sponge, unchanged, along with an error.
+    ///
+    /// Unlike `absorb`, this does not require `U` to be an `ArraySize`, so that it can be used for lengths
+    /// `hybrid_array` does not support, without putting large arrays on the stack. Successive calls add up
+    /// at the type level: two calls with `U524288` consume an `Absorb<U1048576>` word of the pattern.
+    #[allow(clippy::type_complexity)]
+    pub fn absorb_slice<U>(
+        mut self,
+        elements: &[A::Value],
+        acc: &mut A::Acc,
+    ) -> Result<ExtraSponge<A, Use<I, Absorb<U>>>, (Self, Error)>
+    where
+        U: Unsigned,
+        I: Consume<Absorb<U>>,
+    {
+        if elements.len() != U::USIZE {
+            let err = Error::LengthMismatch {
+                expected: U::USIZE,
+                actual: elements.len(),
+            };
+            return Err((self, err));
+        }
+        self.api.absorb(U::to_u32(), elements, acc);
+        Ok(self.repattern())
+    }
+
+    /// This function is used to absorb the elements of a tuple of arrays in the sponge, in order.
+    /// The lengths of the arrays are summed at compile time, and checked against a single word of the IOPattern.
+    /// The underlying API receives the arrays through `SpongeAPI::absorb_parts`.
+    pub fn absorb_many<T>(
+        mut self,
+        arrays: T,
+        acc: &mut A::Acc,
+    ) -> ExtraSponge<A, Use<I, Absorb<T::Len>>>
+    where
+        T: ArrayTuple<A::Value>,
+        I: Consume<Absorb<T::Len>>,
+    {
+        arrays.with_slices(|parts| self.api.absorb_parts(parts, acc));
+        self.repattern()
+    }
+
+    /// This function is used to absorb the `U` elements yielded by an iterator in the sponge.
+    /// The elements are fed to the underlying API in chunks of bounded size, which the SAFE spec allows
+    /// as successive absorb calls aggregate into a single IOPattern word.
+    ///
+    /// If the iterator yields more or fewer than `U` elements, this returns an error along with the sponge, from
+    /// which nothing was absorbed. An iterator whose `size_hint` gives its exact length (e.g. an `ExactSizeIterator`)
+    /// is streamed chunk by chunk, while the elements of any other iterator are first buffered in full, so that its
+    /// length is checked before anything is absorbed.
+    ///
+    /// This panics if an iterator yields more or fewer elements than its `size_hint` gives.
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "alloc")))]
+    #[allow(clippy::type_complexity)]
+    pub fn absorb_iter<U, It>(
+        mut self,
+        elements: It,
+        acc: &mut A::Acc,
+    ) -> Result<ExtraSponge<A, Use<I, Absorb<U>>>, (Self, Error)>
+    where
+        U: Unsigned,
+        It: IntoIterator<Item = A::Value>,
+        I: Consume<Absorb<U>>,
+    {
+        let mut elements = elements.into_iter();
+        let mismatch = |actual| Error::LengthMismatch {
+            expected: U::USIZE,
+            actual,
+        };
+
+        match elements.size_hint() {
+            (lower, _) if lower > U::USIZE => return Err((self, mismatch(lower))),
+            (_, Some(upper)) if upper < U::USIZE => return Err((self, mismatch(upper))),
+            (lower, Some(upper)) if lower == upper => {
+                if !self.absorb_chunks(U::USIZE, &mut elements, acc) {
+                    // the sponge no longer follows its pattern, and must be finished before unwinding drops it
+                    let _ = self.finish_unchecked();
+                    panic!("the iterator does not yield as many elements as its size_hint gives");
+                }
+                return Ok(self.repattern());
+            }
+            _ => (),
+        }
+
+        let buffer: Vec<A::Value> = elements.take(U::USIZE + 1).collect();
+        if buffer.len() != U::USIZE {
+            return Err((self, mismatch(buffer.len())));
+        }
+        self.absorb_chunks(U::USIZE, &mut buffer.into_iter(), acc);
+        Ok(self.repattern())
+    }
+
+    // Absorbs `len` elements of `elements`, in chunks of at most `ABSORB_ITER_CHUNK` elements, and returns whether the
+    // iterator yielded exactly `len` elements.
+    #[cfg(feature = "alloc")]
+    fn absorb_chunks(
+        &mut self,
+        len: usize,
+        elements: &mut impl Iterator<Item = A::Value>,
+        acc: &mut A::Acc,
+    ) -> bool {
+        let mut chunk = Vec::with_capacity(len.min(ABSORB_ITER_CHUNK));
+        let mut absorbed = 0;
+        while absorbed < len {
+            chunk.clear();
+            chunk.extend(
+                elements
+                    .by_ref()
+                    .take((len - absorbed).min(ABSORB_ITER_CHUNK)),
+            );
+            if chunk.is_empty() {
+                return false;
+            }
+            self.api.absorb(chunk.len() as u32, &chunk, acc);
+            absorbed += chunk.len();
+        }
+        elements.next().is_none()
+    }
 }
 
+// The maximal number of elements absorbed in a single call to the underlying API by `ExtraSponge::absorb_iter`
+#[cfg(feature = "alloc")]
+const ABSORB_ITER_CHUNK: usize = 256;
+
 impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I> {
     /// This pass-through function is used to squeeze elements out of the sponge.
     /// It calls the underlying API's squeeze function, and then returns a new ExtraSponge
@@ -212,6 +951,162 @@ impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I> {
         self.api.squeeze(U::to_u32(), harray.as_mut_slice(), acc);
         self.repattern()
     }
+
+    /// This function is used to squeeze elements out of the sponge into a tuple of mutable references to arrays, in order.
+    /// The lengths of the arrays are summed at compile time, and checked against a single word of the IOPattern.
+    /// The underlying API receives the arrays through `SpongeAPI::squeeze_parts`.
+    pub fn squeeze_into<T>(
+        mut self,
+        arrays: T,
+        acc: &mut A::Acc,
+    ) -> ExtraSponge<A, Use<I, Squeeze<T::Len>>>
+    where
+        T: ArrayTupleMut<A::Value>,
+        I: Consume<Squeeze<T::Len>>,
+    {
+        arrays.with_slices_mut(|parts| self.api.squeeze_parts(parts, acc));
+        self.repattern()
+    }
+
+    /// This function is used to squeeze elements out of the sponge into a slice, whose length is checked at runtime
+    /// to be `U`. If it is not, this returns the sponge, unchanged, along with an error.
+    ///
+    /// As for `absorb_slice`, this does not require `U` to be an `ArraySize`, and successive calls add up
+    /// at the type level.
+    #[allow(clippy::type_complexity)]
+    pub fn squeeze_slice<U>(
+        mut self,
+        elements: &mut [A::Value],
+        acc: &mut A::Acc,
+    ) -> Result<ExtraSponge<A, Use<I, Squeeze<U>>>, (Self, Error)>
+    where
+        U: Unsigned,
+        I: Consume<Squeeze<U>>,
+    {
+        if elements.len() != U::USIZE {
+            let err = Error::LengthMismatch {
+                expected: U::USIZE,
+                actual: elements.len(),
+            };
+            return Err((self, err));
+        }
+        self.api.squeeze(U::to_u32(), elements, acc);
+        Ok(self.repattern())
+    }
+}
+
+#[allow(clippy::type_complexity)]
+impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I> {
+    /// This function squeezes elements out of the sponge into a new array, which it returns along with the new
+    /// ExtraSponge.
+    /// Unlike `squeeze`, this does not require the caller to initialize an output array beforehand, nor the values
+    /// to have a default.
+    /// The underlying API fills the array through `SpongeAPI::squeeze_array`.
+    pub fn squeeze_array<U>(
+        mut self,
+        acc: &mut A::Acc,
+    ) -> (Array<A::Value, U>, ExtraSponge<A, Use<I, Squeeze<U>>>)
+    where
+        U: ArraySize<A::Value>,
+        I: Consume<Squeeze<U>>,
+    {
+        let harray = self.api.squeeze_array(acc);
+        (harray, self.repattern())
+    }
+}
+
+#[allow(clippy::type_complexity)]
+impl<A: SpongeAPI, I: Normalize> ExtraSponge<A, I>
+where
+    A::Value: BytePacking,
+{
+    /// This function absorbs bytes in the sponge, packing them into `A::Value` elements
+    /// as described by the `BytePacking` implementation of the latter.
+    /// The consumed pattern is expressed in elements: `NBytes` bytes count for `Packed<NBytes, A::Value>`
+    /// elements, a number computed at compile time.
+    ///
+    /// **The number of bytes is not absorbed.** The last element is right-padded with zeroes, and byte counts which
+    /// pack into the same number of elements share the same pattern, hence the same tag. Over `u64` values, for
+    /// instance, `b"a"` and `b"a\0"` are absorbed identically, as are any 9 to 16 bytes which agree once padded to 16.
+    /// Callers which absorb inputs of varying lengths must encode their length themselves, e.g. by absorbing it first.
+    pub fn absorb_bytes<NBytes>(
+        self,
+        bytes: Array<u8, NBytes>,
+        acc: &mut A::Acc,
+    ) -> ExtraSponge<A, Use<I, Absorb<Packed<NBytes, A::Value>>>>
+    where
+        NBytes: ArraySize<u8> + PackedLength<<A::Value as BytePacking>::Width>,
+        Packed<NBytes, A::Value>: ArraySize<A::Value>,
+        I: Consume<Absorb<Packed<NBytes, A::Value>>>,
+    {
+        let mut elements = array_from_fn(|_| A::Value::pack(&[]));
+        codec::pack_bytes(bytes.as_slice(), elements.as_mut_slice());
+        self.absorb(elements, acc)
+    }
+
+    /// This function squeezes bytes out of the sponge, unpacking them from `A::Value` elements
+    /// as described by the `BytePacking` implementation of the latter.
+    /// The consumed pattern is expressed in elements: `NBytes` bytes count for `Packed<NBytes, A::Value>`
+    /// elements, a number computed at compile time.
+    pub fn squeeze_bytes<NBytes>(
+        self,
+        bytes: &mut Array<u8, NBytes>,
+        acc: &mut A::Acc,
+    ) -> ExtraSponge<A, Use<I, Squeeze<Packed<NBytes, A::Value>>>>
+    where
+        NBytes: ArraySize<u8> + PackedLength<<A::Value as BytePacking>::Width>,
+        Packed<NBytes, A::Value>: ArraySize<A::Value>,
+        I: Consume<Squeeze<Packed<NBytes, A::Value>>>,
+    {
+        // these placeholder elements are overwritten by the squeeze
+        let mut elements = array_from_fn(|_| A::Value::pack(&[]));
+        let res = self.squeeze(&mut elements, acc);
+        codec::unpack_bytes(elements.as_slice(), bytes.as_mut_slice());
+        res
+    }
+}
+
+// hybrid_array does not offer a way to build an array of generic size, so we write the elements one by one.
+fn array_from_fn<T, U: ArraySize<T>>(mut f: impl FnMut(usize) -> T) -> Array<T, U> {
+    let mut res = MaybeUninit::<Array<T, U>>::uninit();
+    let ptr = res.as_mut_ptr() as *mut T;
+    for i in 0..U::USIZE {
+        // Safety: Array<T, U> is a transparent wrapper around [T; U::USIZE], so this is in bounds
+        unsafe { ptr.add(i).write(f(i)) };
+    }
+    // Safety: all elements have been initialized above
+    unsafe { res.assume_init() }
+}
+
+/// Squeezes `U` elements from `sponge` into a new array, as a single IO pattern word, by filling the array with
+/// default values which `SpongeAPI::squeeze` then overwrites.
+/// This is an implementation of `SpongeAPI::squeeze_array` for the sponges whose values have a default, and of
+/// `NativeSpongeAPI::squeeze_array` with a unit accumulator.
+pub fn squeeze_array_by_default<S, U>(sponge: &mut S, acc: &mut S::Acc) -> Array<S::Value, U>
+where
+    S: SpongeAPI,
+    S::Value: Default,
+    U: ArraySize<S::Value>,
+{
+    let mut res = array_from_fn(|_| S::Value::default());
+    sponge.squeeze(U::to_u32(), res.as_mut_slice(), acc);
+    res
+}
+
+// Builds an array without initializing its elements first, letting `fill` write them.
+// Safety: `fill` must initialize every element of the slice it is given.
+pub(crate) unsafe fn array_from_uninit<T, U: ArraySize<T>>(
+    fill: impl FnOnce(&mut [MaybeUninit<T>]),
+) -> Array<T, U> {
+    let mut res = MaybeUninit::<Array<T, U>>::uninit();
+    // Safety: Array<T, U> is a transparent wrapper around [T; U::USIZE], and MaybeUninit<T> has the
+    // same layout as T
+    let elements = unsafe {
+        core::slice::from_raw_parts_mut(res.as_mut_ptr() as *mut MaybeUninit<T>, U::USIZE)
+    };
+    fill(elements);
+    // Safety: the caller guarantees all elements were initialized
+    unsafe { res.assume_init() }
 }
 
 /// This implementation of drop is called automatically when the ExtraSponge drops out of scope.
@@ -224,7 +1119,10 @@ impl<A: SpongeAPI, I: List> Drop for ExtraSponge<A, I> {
                 .expect("SpongeAPI invariant violated: finish failed on an empty IO pattern");
         } else {
             // TODO: find a better behavior than a panic, here: this induces aborts!
-            panic!("SpongeAPI invariant violated: forgot to empty IO pattern before dropping it");
+            panic!(
+                "SpongeAPI invariant violated: forgot to empty IO pattern before dropping it, remaining: {}",
+                PatternDisplay::<I>::new()
+            );
         }
     }
 }
@@ -236,7 +1134,9 @@ pub mod unit_tests;
 #[cfg(test)]
 mod tests {
     use super::*;
-    use typenum::{U3, U5};
+    use std::prelude::rust_2021::*;
+    use std::{format, vec};
+    use typenum::{U2, U3, U5};
 
     #[test]
     fn test_to_sponge_op() {
@@ -244,12 +1144,85 @@ mod tests {
         assert_eq!(Squeeze::<U5>::to_sponge_op(), SpongeOp::Squeeze(5));
     }
 
+    #[test]
+    fn test_iopattern_consts() {
+        type P = iopat![Absorb<U5>, Squeeze<U3>, Absorb<U2>];
+        assert_eq!(P::LEN, 3);
+        assert_eq!(P::NEXT_OP, Some(SpongeOp::Absorb(5)));
+        assert_eq!(P::TOTAL_ABSORB, 7);
+        assert_eq!(P::TOTAL_SQUEEZE, 3);
+        assert_eq!(Nil::NEXT_OP, None);
+    }
+
     #[test]
     fn test_to_iopattern() {
-        assert_eq!(Nil::to_iopattern(), IOPattern(Vec::default()));
+        assert_eq!(Nil::to_iopattern(), IOPattern::new(Vec::default()));
         assert_eq!(
             <iopat![Absorb::<U5>, Squeeze<U3>]>::to_iopattern(),
-            IOPattern(vec![SpongeOp::Absorb(5), SpongeOp::Squeeze(3)])
+            IOPattern::new(vec![SpongeOp::Absorb(5), SpongeOp::Squeeze(3)])
+        );
+        assert_eq!(
+            <iopat![Absorb::<U5>, Squeeze<U3>]>::to_iopattern(),
+            IOPattern::from_static(&[SpongeOp::Absorb(5), SpongeOp::Squeeze(3)])
+        );
+    }
+
+    // A sponge recording the calls it receives
+    #[derive(Debug, Default)]
+    struct Recorder(Vec<SpongeOp>);
+
+    impl NativeSpongeAPI for Recorder {
+        type Value = u8;
+
+        fn start(&mut self, _p: IOPattern, _domain_separator: Option<u32>) {}
+
+        fn absorb(&mut self, length: u32, _elements: &[u8]) {
+            self.0.push(SpongeOp::Absorb(length));
+        }
+
+        fn squeeze(&mut self, length: u32, _elements: &mut [u8]) {
+            self.0.push(SpongeOp::Squeeze(length));
+        }
+
+        fn squeeze_array<U: ArraySize<u8>>(&mut self) -> Array<u8, U> {
+            squeeze_array_by_default(self, &mut ())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_absorb_parts_skips_empty_parts() {
+        let mut sponge = Recorder::default();
+        sponge.absorb_parts(&[&[1, 2], &[], &[3]], &mut ());
+        assert_eq!(sponge.0, [SpongeOp::Absorb(2), SpongeOp::Absorb(1)]);
+    }
+
+    #[test]
+    fn test_squeeze_parts_skips_empty_parts() {
+        let mut sponge = Recorder::default();
+        let (mut x, mut y) = ([0u8; 2], [0u8; 3]);
+        sponge.squeeze_parts(&mut [&mut [], &mut x, &mut [], &mut y], &mut ());
+        assert_eq!(sponge.0, [SpongeOp::Squeeze(2), SpongeOp::Squeeze(3)]);
+    }
+
+    #[test]
+    fn test_iopattern_repr() {
+        type P = iopat![Absorb<U5>, Squeeze<U3>, Absorb<U2>];
+        let p = P::to_iopattern();
+        assert_eq!(p.len(), 3);
+        assert_eq!(p.first(), Some(SpongeOp::Absorb(5)));
+        assert_eq!(p.iter().len(), 3);
+        assert_eq!(
+            format!("{p:?}"),
+            "IOPattern([Absorb(5), Squeeze(3), Absorb(2)])"
+        );
+        assert_eq!(
+            format!("{:?}", P::OPS),
+            "[Absorb(5), Squeeze(3), Absorb(2)]"
         );
+        assert_ne!(p, IOPattern::from_static(&[SpongeOp::Absorb(5)]));
     }
 }
@@ -0,0 +1,90 @@
+//! This module contains a wrapper around the ExtraSponge which owns the accumulator of the sponge
+//! for the whole lifetime of the latter, so that the absorb and squeeze operations do not need to be passed one.
+//! This is mostly useful for native hashing, where the accumulator is meaningless, see [`crate::NativeSpongeAPI`].
+
+use core::fmt;
+
+use hybrid_array::{Array, ArraySize};
+
+use crate::traits::{Absorb, Consume, List, Nil, Norm, Normalize, Squeeze, Use};
+use crate::{Error, ExtraSponge, SpongeAPI, ToIOPattern};
+
+/// An ExtraSponge bundled with the accumulator it operates on.
+/// As for the ExtraSponge, the IOPattern I is tracked at the type level, and the sponge
+/// must be emptied (and finished, which returns the accumulator) before being dropped.
+pub struct AccSponge<A: SpongeAPI, I: List> {
+    sponge: ExtraSponge<A, I>,
+    acc: A::Acc,
+}
+
+impl<A: SpongeAPI, I: Normalize> AccSponge<A, I>
+where
+    Norm<I>: ToIOPattern, // Satisfied in all cases
+{
+    /// Creates a sponge with the IOPattern given as a type parameter, which takes
+    /// ownership of the accumulator `acc`. See `ExtraSponge::start`.
+    pub fn start(domain_separator: Option<u32>, api: A, mut acc: A::Acc) -> AccSponge<A, Norm<I>> {
+        let sponge = ExtraSponge::<A, I>::start(domain_separator, api, &mut acc);
+        AccSponge { sponge, acc }
+    }
+}
+
+impl<A: SpongeAPI, I: Normalize> AccSponge<A, I> {
+    /// Absorbs elements in the sponge, see `ExtraSponge::absorb`.
+    pub fn absorb<U>(self, harray: Array<A::Value, U>) -> AccSponge<A, Use<I, Absorb<U>>>
+    where
+        U: ArraySize<A::Value>,
+        I: Consume<Absorb<U>>,
+    {
+        let AccSponge { sponge, mut acc } = self;
+        let sponge = sponge.absorb(harray, &mut acc);
+        AccSponge { sponge, acc }
+    }
+
+    /// Squeezes elements out of the sponge, see `ExtraSponge::squeeze`.
+    pub fn squeeze<U>(self, harray: &mut Array<A::Value, U>) -> AccSponge<A, Use<I, Squeeze<U>>>
+    where
+        U: ArraySize<A::Value>,
+        I: Consume<Squeeze<U>>,
+    {
+        let AccSponge { sponge, mut acc } = self;
+        let sponge = sponge.squeeze(harray, &mut acc);
+        AccSponge { sponge, acc }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+impl<A: SpongeAPI, I: Normalize> AccSponge<A, I> {
+    /// Squeezes elements out of the sponge into a fresh array, see `ExtraSponge::squeeze_array`.
+    pub fn squeeze_array<U>(self) -> (Array<A::Value, U>, AccSponge<A, Use<I, Squeeze<U>>>)
+    where
+        U: ArraySize<A::Value>,
+        I: Consume<Squeeze<U>>,
+    {
+        let AccSponge { sponge, mut acc } = self;
+        let (harray, sponge) = sponge.squeeze_array(&mut acc);
+        (harray, AccSponge { sponge, acc })
+    }
+}
+
+impl<A: SpongeAPI> AccSponge<A, Nil> {
+    /// Finishes the sponge, which must have an empty IOPattern by then, and returns its accumulator, or the error
+    /// returned by the underlying sponge.
+    pub fn finish(self) -> Result<A::Acc, Error> {
+        let AccSponge { sponge, acc } = self;
+        sponge.finish()?;
+        Ok(acc)
+    }
+}
+
+impl<A: SpongeAPI + fmt::Debug, I: List> fmt::Debug for AccSponge<A, I>
+where
+    A::Acc: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccSponge")
+            .field("sponge", &self.sponge)
+            .field("acc", &self.acc)
+            .finish()
+    }
+}
@@ -0,0 +1,384 @@
+//! This module contains the parsing of term-level [`SpongeOp`]s and [`IOPattern`]s from text.
+//! The following notations are accepted, and can be mixed:
+//! - the format produced by the `Display` implementations, e.g. `Absorb(5), Squeeze(3)`,
+//! - the notation of the SAFE spec, e.g. `ABSORB(5) SQUEEZE(3)`,
+//! - a compact notation, e.g. `A5,S3`.
+//!
+//! Operation names are case-insensitive, and operations may be separated by commas or whitespace.
+//! A parenthesized sequence of operations may be repeated, e.g. `(A2,S1)*32`. Such groups must not be empty, and
+//! may be nested up to 32 levels deep.
+//! Parse errors point to the span of the input at which parsing failed.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+
+use crate::{IOPattern, SpongeOp};
+
+/// The error returned when parsing a [`SpongeOp`] or an [`IOPattern`] fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParsePatternError {
+    span: Range<usize>,
+    message: &'static str,
+}
+
+impl ParsePatternError {
+    fn new(span: Range<usize>, message: &'static str) -> Self {
+        ParsePatternError { span, message }
+    }
+
+    /// The byte range of the input at which parsing failed.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for ParsePatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}..{}",
+            self.message, self.span.start, self.span.end
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParsePatternError {}
+
+// A cursor over the input, which keeps track of the position for error reporting.
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    // the number of groups the cursor is in
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Parser {
+            input,
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn is_done(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    // consumes `c` if it is the next (non-whitespace) character
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, message: &'static str) -> Result<(), ParsePatternError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error_at_next(message))
+        }
+    }
+
+    // consumes the longest prefix of characters satisfying `pred`, returning it along with its span
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> (&'a str, Range<usize>) {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let len = rest.find(|c| !pred(c)).unwrap_or(rest.len());
+        let span = self.pos..self.pos + len;
+        self.pos += len;
+        (&rest[..len], span)
+    }
+
+    // an error spanning the next character of the input, or its end
+    fn error_at_next(&mut self, message: &'static str) -> ParsePatternError {
+        self.skip_whitespace();
+        let len = self.rest().chars().next().map_or(0, char::len_utf8);
+        ParsePatternError::new(self.pos..self.pos + len, message)
+    }
+
+    fn parse_count(&mut self) -> Result<u32, ParsePatternError> {
+        let (digits, span) = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            return Err(self.error_at_next("expected a number"));
+        }
+        digits
+            .parse()
+            .map_err(|_| ParsePatternError::new(span, "number too large"))
+    }
+
+    fn parse_op(&mut self) -> Result<SpongeOp, ParsePatternError> {
+        let (name, span) = self.take_while(|c| c.is_ascii_alphabetic());
+        let op: fn(u32) -> SpongeOp = match name.to_ascii_lowercase().as_str() {
+            "absorb" | "a" => SpongeOp::Absorb,
+            "squeeze" | "s" => SpongeOp::Squeeze,
+            "" => return Err(self.error_at_next("expected an operation")),
+            _ => return Err(ParsePatternError::new(span, "unknown operation")),
+        };
+        // the count is either parenthesized, or directly follows the name in the compact notation
+        if self.eat('(') {
+            let count = self.parse_count()?;
+            self.expect(')', "expected `)`")?;
+            Ok(op(count))
+        } else if self.rest().starts_with(|c: char| c.is_ascii_digit()) {
+            Ok(op(self.parse_count()?))
+        } else {
+            Err(self.error_at_next("expected `(` or a number"))
+        }
+    }
+
+    // parses a single operation, or a parenthesized, possibly repeated, sequence of operations
+    fn parse_item(&mut self, ops: &mut Vec<SpongeOp>) -> Result<(), ParsePatternError> {
+        if !self.eat('(') {
+            ops.push(self.parse_op()?);
+            return Ok(());
+        }
+        let start = self.pos - 1;
+        if self.depth == MAX_NESTING_DEPTH {
+            return Err(ParsePatternError::new(
+                start..self.pos,
+                "groups nested too deeply",
+            ));
+        }
+        if self.rest().trim_start().starts_with(')') {
+            return Err(self.error_at_next("expected an operation"));
+        }
+        self.depth += 1;
+        let group = self.parse_sequence(true)?;
+        self.depth -= 1;
+        self.expect(')', "expected `)`")?;
+        let repetitions = if self.eat('*') {
+            self.parse_count()? as usize
+        } else {
+            1
+        };
+        let span = start..self.pos;
+        match group.len().checked_mul(repetitions) {
+            // a group may be empty once expanded, e.g. `((A1)*0)*1000`, and is then not repeated at all
+            Some(0) => Ok(()),
+            Some(len) if ops.len() + len <= MAX_PATTERN_LEN => {
+                for _ in 0..repetitions {
+                    ops.extend_from_slice(&group);
+                }
+                Ok(())
+            }
+            _ => Err(ParsePatternError::new(span, "pattern too long")),
+        }
+    }
+
+    // parses operations up to the end of the input, or up to a closing parenthesis if `nested`
+    fn parse_sequence(&mut self, nested: bool) -> Result<Vec<SpongeOp>, ParsePatternError> {
+        let mut ops = Vec::new();
+        loop {
+            if self.is_done() || (nested && self.rest().starts_with(')')) {
+                return Ok(ops);
+            }
+            self.parse_item(&mut ops)?;
+            // separators are optional between operations, but a comma must be followed by one
+            if self.eat(',') && (self.is_done() || self.rest().starts_with(')')) {
+                return Err(self.error_at_next("expected an operation"));
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), ParsePatternError> {
+        if self.is_done() {
+            Ok(())
+        } else {
+            Err(ParsePatternError::new(
+                self.pos..self.input.len(),
+                "unexpected trailing input",
+            ))
+        }
+    }
+}
+
+impl FromStr for SpongeOp {
+    type Err = ParsePatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let op = parser.parse_op()?;
+        parser.finish()?;
+        Ok(op)
+    }
+}
+
+// The maximal number of operations in a parsed pattern, once repetitions are expanded
+const MAX_PATTERN_LEN: usize = 1 << 16;
+// The maximal number of nested groups, which bounds the recursion of the parser
+const MAX_NESTING_DEPTH: usize = 32;
+
+impl FromStr for IOPattern {
+    type Err = ParsePatternError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(s);
+        let ops = parser.parse_sequence(false)?;
+        parser.finish()?;
+        Ok(IOPattern::new(ops))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+    use std::{format, vec};
+
+    #[test]
+    fn parses_ops() {
+        assert_eq!("Absorb(5)".parse(), Ok(SpongeOp::Absorb(5)));
+        assert_eq!(" Squeeze ( 3 ) ".parse(), Ok(SpongeOp::Squeeze(3)));
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!("".parse(), Ok(IOPattern::new(vec![])));
+        assert_eq!(
+            "Absorb(5), Squeeze(3)".parse(),
+            Ok(IOPattern::new(vec![
+                SpongeOp::Absorb(5),
+                SpongeOp::Squeeze(3)
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_safe_notation() {
+        assert_eq!(
+            "ABSORB(5) SQUEEZE(3)".parse(),
+            Ok(IOPattern::new(vec![
+                SpongeOp::Absorb(5),
+                SpongeOp::Squeeze(3)
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_compact_notation() {
+        assert_eq!(
+            "A5,S3".parse(),
+            Ok(IOPattern::new(vec![
+                SpongeOp::Absorb(5),
+                SpongeOp::Squeeze(3)
+            ]))
+        );
+        assert_eq!("s12".parse(), Ok(SpongeOp::Squeeze(12)));
+    }
+
+    #[test]
+    fn parses_repetitions() {
+        let pattern: IOPattern = "A1, (A2,S1)*32, S4".parse().unwrap();
+        assert_eq!(pattern.len(), 66);
+        assert_eq!(
+            pattern.to_vec()[..3],
+            [
+                SpongeOp::Absorb(1),
+                SpongeOp::Absorb(2),
+                SpongeOp::Squeeze(1)
+            ]
+        );
+        assert_eq!(
+            pattern.to_vec()[63..],
+            [
+                SpongeOp::Absorb(2),
+                SpongeOp::Squeeze(1),
+                SpongeOp::Squeeze(4)
+            ]
+        );
+        // nested and unrepeated groups
+        assert_eq!(
+            "((A1)*2 S1)*2 (S2)".parse(),
+            Ok(IOPattern::new(vec![
+                SpongeOp::Absorb(1),
+                SpongeOp::Absorb(1),
+                SpongeOp::Squeeze(1),
+                SpongeOp::Absorb(1),
+                SpongeOp::Absorb(1),
+                SpongeOp::Squeeze(1),
+                SpongeOp::Squeeze(2),
+            ]))
+        );
+        assert_eq!("(A1)*0".parse(), Ok(IOPattern::new(vec![])));
+    }
+
+    #[test]
+    fn rejects_empty_groups() {
+        let err = "A1 ()*4000000000".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 4..5);
+        assert_eq!(err.to_string(), "expected an operation at 4..5");
+        let err = "(( ))".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 3..4);
+        // groups which are empty once expanded are not repeated
+        assert_eq!(
+            "((A1)*0)*4000000000 S1".parse(),
+            Ok(IOPattern::new(vec![SpongeOp::Squeeze(1)]))
+        );
+    }
+
+    #[test]
+    fn bounds_nesting() {
+        let nested = |depth| format!("{}A1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(
+            nested(MAX_NESTING_DEPTH).parse(),
+            Ok(IOPattern::new(vec![SpongeOp::Absorb(1)]))
+        );
+        let err = nested(MAX_NESTING_DEPTH + 1)
+            .parse::<IOPattern>()
+            .unwrap_err();
+        assert_eq!(err.span(), MAX_NESTING_DEPTH..MAX_NESTING_DEPTH + 1);
+        assert_eq!(err.to_string(), "groups nested too deeply at 32..33");
+        // deep enough to overflow the stack without the bound
+        assert!(nested(1 << 20).parse::<IOPattern>().is_err());
+    }
+
+    #[test]
+    fn roundtrips() {
+        let pattern = IOPattern::new(vec![
+            SpongeOp::Absorb(1),
+            SpongeOp::Squeeze(0),
+            SpongeOp::Absorb(4_000_000_000),
+        ]);
+        assert_eq!(pattern.to_string().parse(), Ok(pattern));
+    }
+
+    #[test]
+    fn reports_spans() {
+        let err = "Absorb(5), Sqeeze(3)".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 11..17);
+        let err = "Absorb(5) Squeeze 3)".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 19..20);
+        let err = "A5,".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 3..3);
+        let err = "(A2,S1*32".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 6..7);
+        let err = "A5 (A2,S1)*4000000000".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 3..21);
+        let err = "A5 Q3".parse::<IOPattern>().unwrap_err();
+        assert_eq!(err.span(), 3..4);
+        assert_eq!(err.to_string(), "unknown operation at 3..4");
+        let err = "Absorb(99999999999)".parse::<SpongeOp>().unwrap_err();
+        assert_eq!(err.span(), 7..18);
+        let err = "Absorb(5".parse::<SpongeOp>().unwrap_err();
+        assert_eq!(err.span(), 8..8);
+        assert_eq!(err.to_string(), "expected `)` at 8..8");
+    }
+}
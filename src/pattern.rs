@@ -0,0 +1,275 @@
+//! This module contains the term-level counterparts of the type-level operations on IOPatterns of [`crate::traits`].
+//! [`IOPattern::normalize`] mirrors `Normalize`, and [`IOPattern::consume`] mirrors `Consume`, with the same
+//! zero-elision semantics, so that dynamic code can reason about patterns the same way the type checker does.
+
+use alloc::vec::Vec;
+use core::iter;
+
+use crate::{Error, IOPattern, SpongeOp};
+
+impl IOPattern {
+    /// Returns a builder for a pattern, e.g. `IOPattern::builder().absorb(5).squeeze(3).build()`.
+    pub fn builder() -> IOPatternBuilder {
+        IOPatternBuilder::default()
+    }
+
+    /// Returns the normal form of the pattern, where words with a zero count are removed and
+    /// successive words of the same kind are merged, as `Norm` does at the type level.
+    /// This returns an error if a merged word counts more than `u32::MAX` elements.
+    pub fn normalize(&self) -> Result<IOPattern, Error> {
+        let mut ops: Vec<SpongeOp> = Vec::with_capacity(self.len());
+        for op in self {
+            match (ops.last_mut(), op) {
+                (_, SpongeOp::Absorb(0) | SpongeOp::Squeeze(0)) => (),
+                (Some(SpongeOp::Absorb(n)), SpongeOp::Absorb(m))
+                | (Some(SpongeOp::Squeeze(n)), SpongeOp::Squeeze(m)) => merge(n, m)?,
+                _ => ops.push(op),
+            }
+        }
+        Ok(IOPattern::new(ops))
+    }
+
+    /// Returns whether the pattern is in normal form, see `normalize`.
+    pub fn is_normalized(&self) -> bool {
+        // a pattern whose words overflow once merged has successive words of the same kind
+        self.normalize().is_ok_and(|pattern| *self == pattern)
+    }
+
+    /// Returns the concatenation of the two patterns, which is not normalized.
+    pub fn concat(&self, other: &IOPattern) -> IOPattern {
+        self.iter().chain(other).collect()
+    }
+
+    /// Returns the pattern left after performing the operation `op`, as `Use` does at the type level.
+    /// As for the latter, the pattern is assumed to be in head-normal form, and the head of the result is normalized,
+    /// see `head_normalize`, unless the operation has a zero count, in which case the pattern is returned as is.
+    /// This returns an error if the head of the pattern does not allow the operation, or if the normalized head counts
+    /// more than `u32::MAX` elements.
+    pub fn consume(&self, op: SpongeOp) -> Result<IOPattern, Error> {
+        let mismatch = || Error::UnexpectedOperation {
+            expected: self.first(),
+            actual: op,
+        };
+        let mut ops = self.iter();
+        let head = ops.next().ok_or_else(mismatch)?;
+        let rest = match (head, op) {
+            (SpongeOp::Absorb(_), SpongeOp::Absorb(0))
+            | (SpongeOp::Squeeze(_), SpongeOp::Squeeze(0)) => return Ok(self.clone()),
+            (SpongeOp::Absorb(n), SpongeOp::Absorb(m)) if n >= m => SpongeOp::Absorb(n - m),
+            (SpongeOp::Squeeze(n), SpongeOp::Squeeze(m)) if n >= m => SpongeOp::Squeeze(n - m),
+            _ => return Err(mismatch()),
+        };
+        IOPattern::head_normalize(iter::once(rest).chain(ops))
+    }
+
+    // Returns the pattern of `ops` where zero words are removed and words of the same kind are merged until the first
+    // word cannot be merged with the following one, as `HeadNormalize` does at the type level.
+    fn head_normalize(mut ops: impl Iterator<Item = SpongeOp>) -> Result<IOPattern, Error> {
+        let mut head: Option<SpongeOp> = None;
+        let mut res = Vec::with_capacity(ops.size_hint().0);
+        for op in ops.by_ref() {
+            match (&mut head, op) {
+                (_, SpongeOp::Absorb(0) | SpongeOp::Squeeze(0)) => (),
+                (None, _) => head = Some(op),
+                (Some(SpongeOp::Absorb(n)), SpongeOp::Absorb(m))
+                | (Some(SpongeOp::Squeeze(n)), SpongeOp::Squeeze(m)) => merge(n, m)?,
+                (Some(_), _) => {
+                    res.push(op);
+                    break;
+                }
+            }
+        }
+        Ok(IOPattern::new(
+            head.into_iter().chain(res).chain(ops).collect(),
+        ))
+    }
+
+    /// Returns the pattern left after performing the operations of `prefix`, in order, on the normal form of this
+    /// pattern. Zero-count operations of the prefix are ignored. This returns `None` if `prefix` is not a prefix
+    /// of this pattern, once normalized.
+    pub fn strip_prefix(&self, prefix: &IOPattern) -> Option<IOPattern> {
+        prefix
+            .normalize()
+            .ok()?
+            .iter()
+            .try_fold(self.normalize().ok()?, |pattern, op| {
+                pattern.consume(op).ok()
+            })
+    }
+
+    /// Returns whether performing the operations of this pattern is a legal start of `other`, see `strip_prefix`.
+    pub fn is_prefix_of(&self, other: &IOPattern) -> bool {
+        other.strip_prefix(self).is_some()
+    }
+
+    /// Returns the total number of elements absorbed over the pattern.
+    pub fn total_absorb(&self) -> u64 {
+        self.iter()
+            .map(|op| match op {
+                SpongeOp::Absorb(n) => n as u64,
+                SpongeOp::Squeeze(_) => 0,
+            })
+            .sum()
+    }
+
+    /// Returns the total number of elements squeezed over the pattern.
+    pub fn total_squeeze(&self) -> u64 {
+        self.iter()
+            .map(|op| match op {
+                SpongeOp::Absorb(_) => 0,
+                SpongeOp::Squeeze(n) => n as u64,
+            })
+            .sum()
+    }
+}
+
+// Merges the count `m` into the count `n` of a word of the same kind.
+fn merge(n: &mut u32, m: u32) -> Result<(), Error> {
+    *n = n.checked_add(m).ok_or(Error::WordOverflow)?;
+    Ok(())
+}
+
+/// A builder for [`IOPattern`]s, which records operations in order.
+/// As for the `iopat!` macro, the built pattern is not normalized.
+#[derive(Clone, Debug, Default)]
+pub struct IOPatternBuilder {
+    ops: Vec<SpongeOp>,
+}
+
+impl IOPatternBuilder {
+    /// Appends an absorb operation of `n` elements.
+    pub fn absorb(mut self, n: u32) -> Self {
+        self.ops.push(SpongeOp::Absorb(n));
+        self
+    }
+
+    /// Appends a squeeze operation of `n` elements.
+    pub fn squeeze(mut self, n: u32) -> Self {
+        self.ops.push(SpongeOp::Squeeze(n));
+        self
+    }
+
+    /// Appends all the operations of `pattern`.
+    pub fn pattern(mut self, pattern: &IOPattern) -> Self {
+        self.ops.extend(pattern);
+        self
+    }
+
+    /// Returns the pattern of the recorded operations.
+    pub fn build(self) -> IOPattern {
+        IOPattern::new(self.ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+
+    fn pat(s: &str) -> IOPattern {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn builds() {
+        assert_eq!(
+            IOPattern::builder()
+                .absorb(5)
+                .squeeze(3)
+                .pattern(&pat("A1"))
+                .build(),
+            pat("A5, S3, A1")
+        );
+    }
+
+    #[test]
+    fn normalizes() {
+        assert_eq!(pat("A2, A3").normalize().unwrap(), pat("A5"));
+        assert_eq!(pat("S2, A3").normalize().unwrap(), pat("S2, A3"));
+        assert_eq!(pat("A0, S3").normalize().unwrap(), pat("S3"));
+        assert_eq!(pat("A3, S0, A1").normalize().unwrap(), pat("A4"));
+        assert_eq!(pat("S0, A0").normalize().unwrap(), pat(""));
+        assert!(pat("A4, S1").is_normalized());
+        assert!(!pat("A4, A1").is_normalized());
+    }
+
+    #[test]
+    fn rejects_overflowing_words() {
+        let p = pat("A4000000000, A4000000000");
+        assert!(matches!(p.normalize(), Err(Error::WordOverflow)));
+        assert!(!p.is_normalized());
+        assert_eq!(p.strip_prefix(&pat("A1")), None);
+        assert_eq!(
+            pat("S4294967295, S0, S0").normalize().unwrap(),
+            pat("S4294967295")
+        );
+        assert!(matches!(
+            pat("S1, A4000000000, A4000000000").consume(SpongeOp::Squeeze(1)),
+            Err(Error::WordOverflow)
+        ));
+        // only the head is merged
+        assert_eq!(
+            pat("S1, A1, S4000000000, S4000000000")
+                .consume(SpongeOp::Squeeze(1))
+                .unwrap(),
+            pat("A1, S4000000000, S4000000000")
+        );
+    }
+
+    #[test]
+    fn consumes() {
+        let p = pat("A5, S2");
+        assert_eq!(p.consume(SpongeOp::Absorb(2)).unwrap(), pat("A3, S2"));
+        assert_eq!(p.consume(SpongeOp::Absorb(5)).unwrap(), pat("S2"));
+        assert_eq!(p.consume(SpongeOp::Absorb(0)).unwrap(), p);
+        // as at the type level, a zero-count head is not elided before normalization
+        assert_eq!(
+            pat("A0, S2").consume(SpongeOp::Absorb(0)).unwrap(),
+            pat("A0, S2")
+        );
+        assert_eq!(
+            pat("A3, S0, A1").consume(SpongeOp::Absorb(3)).unwrap(),
+            pat("A1")
+        );
+        // only the head of the result is normalized
+        assert_eq!(
+            pat("S3, A5, A1, S0, S2, S1")
+                .consume(SpongeOp::Squeeze(3))
+                .unwrap(),
+            pat("A6, S2, S1")
+        );
+
+        assert!(matches!(
+            p.consume(SpongeOp::Absorb(6)),
+            Err(Error::UnexpectedOperation {
+                expected: Some(SpongeOp::Absorb(5)),
+                actual: SpongeOp::Absorb(6)
+            })
+        ));
+        assert!(p.consume(SpongeOp::Squeeze(0)).is_err());
+        let err = pat("").consume(SpongeOp::Squeeze(1)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unexpected operation Squeeze(1), expected the end of the pattern"
+        );
+    }
+
+    #[test]
+    fn prefixes() {
+        let p = pat("A2, A3, S2, A1");
+        assert_eq!(p.strip_prefix(&pat("A1, A4, S1")), Some(pat("S1, A1")));
+        assert_eq!(p.strip_prefix(&pat("A5, S0, S2, A1")), Some(pat("")));
+        assert_eq!(p.strip_prefix(&pat("")), Some(pat("A5, S2, A1")));
+        assert_eq!(p.strip_prefix(&pat("A4, S2")), None);
+        assert!(pat("A5, S1").is_prefix_of(&p));
+        assert!(!pat("S1").is_prefix_of(&p));
+    }
+
+    #[test]
+    fn totals() {
+        let p = pat("A2, S3, A4000000000, A4000000000");
+        assert_eq!(p.total_absorb(), 8_000_000_002);
+        assert_eq!(p.total_squeeze(), 3);
+        assert_eq!(p.concat(&pat("S1")).total_squeeze(), 4);
+    }
+}
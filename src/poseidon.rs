@@ -0,0 +1,422 @@
+//! This module contains the Poseidon permutation, over the fields of [`crate::field`], along with the generation of
+//! its parameters as the reference implementation (`generate_parameters_grain.sage`) does: the round constants are
+//! sampled from the Grain LFSR seeded with the parameters of the instance, followed by the elements of a Cauchy MDS
+//! matrix, which is resampled until it admits no invariant subspace trail through the partial rounds.
+//!
+//! The instances generated this way are those of the Poseidon paper, which Neptune also implements, e.g. the
+//! `x^5` instances of width 3 and 5 over the scalar field of BLS12-381.
+//! [`PoseidonSponge`] is the SAFE sponge over the permutation.
+
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use hybrid_array::{Array, ArraySize};
+use typenum::Sum;
+
+use crate::array_from_fn;
+use crate::field::{is_permutation_exponent, Field};
+use crate::grain::{GrainLfsr, GrainSBox};
+use crate::sponge::{Additive, Permutation, SafeSponge};
+
+/// A square matrix over `F` of size `W`, as an array of rows.
+pub type Matrix<F, W> = Array<Array<F, W>, W>;
+
+/// The SAFE sponge, absorbing in the mode `Mode`, over a Poseidon permutation of width `Rate + Capacity`.
+pub type PoseidonSponge<F, Rate, Capacity, Mode = Additive> =
+    SafeSponge<Poseidon<F, Sum<Rate, Capacity>>, Rate, Capacity, Mode>;
+
+/// The Poseidon permutation over `F`, with a state of `W` elements.
+/// Each round adds its round constants to the state, applies the S-box `x^alpha` to all the elements of the state
+/// (in full rounds) or to the first one (in partial rounds), and multiplies the state by the MDS matrix.
+/// Half of the full rounds come first, followed by the partial rounds, and by the other half of the full rounds.
+pub struct Poseidon<F, W: ArraySize<F> + ArraySize<Array<F, W>>> {
+    full_rounds: usize,
+    partial_rounds: usize,
+    alpha: u64,
+    // the constants of each round, in order
+    round_constants: Vec<Array<F, W>>,
+    mds: Matrix<F, W>,
+}
+
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> Poseidon<F, W> {
+    /// Returns the instance with `full_rounds` full rounds, `partial_rounds` partial rounds, and the S-box `x^alpha`,
+    /// generating its round constants and MDS matrix from the Grain LFSR.
+    ///
+    /// This panics if the number of full rounds is odd, or if `x^alpha` is not a permutation of the field.
+    pub fn new(full_rounds: usize, partial_rounds: usize, alpha: u64) -> Self {
+        let mut lfsr = GrainLfsr::new(
+            F::MODULUS_BITS,
+            GrainSBox::Power,
+            W::USIZE,
+            full_rounds,
+            partial_rounds,
+        );
+        let round_constants = (0..full_rounds + partial_rounds)
+            .map(|_| array_from_fn(|_| lfsr.next_field_element()))
+            .collect();
+        let mds = loop {
+            if let Some(mds) = cauchy_matrix(&mut lfsr).filter(is_secure) {
+                break mds;
+            }
+        };
+        Self::from_parameters(full_rounds, partial_rounds, alpha, round_constants, mds)
+    }
+
+    /// Returns the instance of the given parameters, which are not checked for security.
+    /// There must be one array of round constants per round.
+    ///
+    /// This panics if the number of full rounds is odd, if `x^alpha` is not a permutation of the field, or if the
+    /// number of arrays of round constants is not the number of rounds.
+    pub fn from_parameters(
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: u64,
+        round_constants: Vec<Array<F, W>>,
+        mds: Matrix<F, W>,
+    ) -> Self {
+        assert!(
+            full_rounds % 2 == 0,
+            "the number of full rounds must be even"
+        );
+        assert!(
+            is_permutation_exponent::<F>(alpha),
+            "x^{alpha} is not a permutation of the field"
+        );
+        assert_eq!(
+            round_constants.len(),
+            full_rounds + partial_rounds,
+            "there must be one array of round constants per round"
+        );
+        Poseidon {
+            full_rounds,
+            partial_rounds,
+            alpha,
+            round_constants,
+            mds,
+        }
+    }
+
+    /// Returns the number of full rounds.
+    pub fn full_rounds(&self) -> usize {
+        self.full_rounds
+    }
+
+    /// Returns the number of partial rounds.
+    pub fn partial_rounds(&self) -> usize {
+        self.partial_rounds
+    }
+
+    /// Returns the exponent of the S-box.
+    pub fn alpha(&self) -> u64 {
+        self.alpha
+    }
+
+    /// Returns the round constants, one array per round.
+    pub fn round_constants(&self) -> &[Array<F, W>] {
+        &self.round_constants
+    }
+
+    /// Returns the MDS matrix.
+    pub fn mds(&self) -> &Matrix<F, W> {
+        &self.mds
+    }
+}
+
+// Arrays of generic size are neither `Clone` nor `Debug`, so those are implemented element-wise.
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> Clone for Poseidon<F, W> {
+    fn clone(&self) -> Self {
+        Poseidon {
+            full_rounds: self.full_rounds,
+            partial_rounds: self.partial_rounds,
+            alpha: self.alpha,
+            round_constants: self.round_constants.iter().map(copy).collect(),
+            mds: array_from_fn(|i| copy(&self.mds[i])),
+        }
+    }
+}
+
+// The constants are not displayed, as they are determined by the other parameters for generated instances.
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> fmt::Debug for Poseidon<F, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poseidon")
+            .field("width", &W::USIZE)
+            .field("full_rounds", &self.full_rounds)
+            .field("partial_rounds", &self.partial_rounds)
+            .field("alpha", &self.alpha)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> Permutation for Poseidon<F, W> {
+    type Element = F;
+    type Width = W;
+
+    fn permute(&self, state: &mut Array<F, W>) {
+        let half = self.full_rounds / 2;
+        for (round, constants) in self.round_constants.iter().enumerate() {
+            for (element, constant) in state.as_mut_slice().iter_mut().zip(constants.as_slice()) {
+                *element = *element + *constant;
+            }
+            if round < half || round >= half + self.partial_rounds {
+                for element in state.as_mut_slice() {
+                    *element = element.pow_u64(self.alpha);
+                }
+            } else {
+                state[0] = state[0].pow_u64(self.alpha);
+            }
+            *state = mul_vec(&self.mds, state);
+        }
+    }
+}
+
+pub(crate) fn copy<F: Copy, W: ArraySize<F>>(v: &Array<F, W>) -> Array<F, W> {
+    array_from_fn(|i| v[i])
+}
+
+pub(crate) fn mul_vec<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>(
+    m: &Matrix<F, W>,
+    v: &Array<F, W>,
+) -> Array<F, W> {
+    array_from_fn(|i| {
+        m[i].as_slice()
+            .iter()
+            .zip(v.as_slice())
+            .fold(F::ZERO, |acc, (a, b)| acc + *a * *b)
+    })
+}
+
+fn mul<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>(
+    a: &Matrix<F, W>,
+    b: &Matrix<F, W>,
+) -> Matrix<F, W> {
+    array_from_fn(|i| {
+        array_from_fn(|j| (0..W::USIZE).fold(F::ZERO, |acc, k| acc + a[i][k] * b[k][j]))
+    })
+}
+
+// Returns the Cauchy matrix `1 / (x_i + y_j)` of the next `2 * W` elements of the LFSR, resampled until they are
+// distinct, or `None` if one of the sums is zero.
+fn cauchy_matrix<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>(
+    lfsr: &mut GrainLfsr,
+) -> Option<Matrix<F, W>> {
+    let elements = loop {
+        let elements: Vec<F> = (0..2 * W::USIZE)
+            .map(|_| lfsr.next_field_element_reduced())
+            .collect();
+        let distinct = elements
+            .iter()
+            .enumerate()
+            .all(|(i, a)| !elements[..i].contains(a));
+        if distinct {
+            break elements;
+        }
+    };
+    let (xs, ys) = elements.split_at(W::USIZE);
+    let mut invertible = true;
+    let res = array_from_fn(|i| {
+        array_from_fn(|j| {
+            (xs[i] + ys[j]).inverse().unwrap_or_else(|| {
+                invertible = false;
+                F::ZERO
+            })
+        })
+    });
+    invertible.then_some(res)
+}
+
+// Returns whether the matrix admits no invariant subspace trail through the partial rounds, whatever their number,
+// following "Proving Resistance Against Infinitely Long Subspace Trails" (Grassi, Rechberger, Schofnegger), as the
+// reference implementation checks for instances with a single S-box per partial round: the first unit vector must
+// span the whole state space under each of the powers `M^r` of the matrix, for `r` up to `4 * W`.
+fn is_secure<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>(mds: &Matrix<F, W>) -> bool {
+    let mut power = array_from_fn(|i| copy(&mds[i]));
+    for _ in 0..4 * W::USIZE {
+        if !spans_space(&power) {
+            return false;
+        }
+        power = mul(&power, mds);
+    }
+    true
+}
+
+// Returns whether the vectors `e_0, M e_0, M^2 e_0, ...` span the whole space.
+fn spans_space<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>(m: &Matrix<F, W>) -> bool {
+    // the basis is kept in echelon form, each vector being normalized to 1 on its pivot
+    let mut basis: Vec<(usize, Array<F, W>)> = Vec::with_capacity(W::USIZE);
+    let mut v: Array<F, W> = array_from_fn(|i| if i == 0 { F::ONE } else { F::ZERO });
+    loop {
+        let mut reduced = copy(&v);
+        for (pivot, b) in &basis {
+            let c = reduced[*pivot];
+            for (x, y) in reduced.as_mut_slice().iter_mut().zip(b.as_slice()) {
+                *x = *x - c * *y;
+            }
+        }
+        let Some(pivot) = reduced.as_slice().iter().position(|x| !x.is_zero()) else {
+            return false;
+        };
+        let inv = reduced[pivot].inverse().unwrap();
+        for x in reduced.as_mut_slice() {
+            *x = *x * inv;
+        }
+        basis.push((pivot, reduced));
+        if basis.len() == W::USIZE {
+            return true;
+        }
+        v = mul_vec(m, &v);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bls12_381Scalar, Goldilocks};
+    use crate::sponge::tag;
+    use crate::traits::{Absorb, Squeeze};
+    use crate::{iopat, ExtraSponge};
+    use hybrid_array::ArrayOps;
+    use std::format;
+    use std::prelude::rust_2021::*;
+    use typenum::{U1, U12, U2, U3, U5};
+
+    fn bls(hex: &str) -> Bls12_381Scalar {
+        let hex = hex.trim_start_matches("0x");
+        let hex = format!("{hex:0>64}");
+        let limbs =
+            [3, 2, 1, 0].map(|i| u64::from_str_radix(&hex[16 * i..16 * (i + 1)], 16).unwrap());
+        Bls12_381Scalar::from_limbs(limbs).unwrap()
+    }
+
+    fn inputs<F: Field, W: ArraySize<F>>() -> Array<F, W> {
+        array_from_fn(|i| F::from_u64(i as u64))
+    }
+
+    // The test vectors of the reference implementation, for the permutation of [0, 1, ..]
+    #[test]
+    fn permutes_bls12_381() {
+        let poseidon = Poseidon::<Bls12_381Scalar, U3>::new(8, 57, 5);
+        assert_eq!(
+            poseidon.round_constants()[0][0],
+            bls("0x6c4ffa723eaf1a7bf74905cc7dae4ca9ff4a2c3bc81d42e09540d1f250910880")
+        );
+        assert_eq!(
+            poseidon.mds()[0][0],
+            bls("0x3d955d6c02fe4d7cb500e12f2b55eff668a7b4386bd27413766713c93f2acfcd")
+        );
+        let mut state = inputs();
+        poseidon.permute(&mut state);
+        assert_eq!(
+            state.as_slice(),
+            [
+                bls("0x28ce19420fc246a05553ad1e8c98f5c9d67166be2c18e9e4cb4b4e317dd2a78a"),
+                bls("0x51f3e312c95343a896cfd8945ea82ba956c1118ce9b9859b6ea56637b4b1ddc4"),
+                bls("0x3b2b69139b235626a0bfb56c9527ae66a7bf486ad8c11c14d1da0c69bbe0f79a"),
+            ]
+        );
+
+        let poseidon = Poseidon::<Bls12_381Scalar, U5>::new(8, 60, 5);
+        let mut state = inputs();
+        poseidon.permute(&mut state);
+        assert_eq!(
+            state.as_slice(),
+            [
+                bls("0x2a918b9c9f9bd7bb509331c81e297b5707f6fc7393dcee1b13901a0b22202e18"),
+                bls("0x65ebf8671739eeb11fb217f2d5c5bf4a0c3f210e3f3cd3b08b5db75675d797f7"),
+                bls("0x2cc176fc26bc70737a696a9dfd1b636ce360ee76926d182390cdb7459cf585ce"),
+                bls("0x4dc4e29d283afd2a491fe6aef122b9a968e74eff05341f3cc23fda1781dcb566"),
+                bls("0x03ff622da276830b9451b88b85e6184fd6ae15c8ab3ee25a5667be8592cce3b1"),
+            ]
+        );
+    }
+
+    // These values are computed by an independent implementation of the reference parameter generation
+    #[test]
+    fn permutes_goldilocks() {
+        let poseidon = Poseidon::<Goldilocks, U12>::new(8, 22, 7);
+        assert_eq!(
+            poseidon.round_constants()[0][0].to_u64(),
+            0x13dc_f33a_ba21_4f46
+        );
+        assert_eq!(poseidon.mds()[0][0].to_u64(), 0x5edf_e0e0_ee54_d262);
+        let mut state = inputs();
+        poseidon.permute(&mut state);
+        let expected = [
+            0x056b_da38_ad30_8e78,
+            0x1f38_9442_38b8_ccd0,
+            0x80be_f63a_171f_3156,
+            0x27bb_c645_b2a3_198c,
+            0x9bef_ae3f_2215_09b3,
+            0xa1cf_a54a_e2c4_4c9e,
+            0xa1c8_7686_9f1c_52f8,
+            0x7ffa_2147_1eff_65af,
+            0xdc56_5450_ad52_b99e,
+            0x4b8b_1daf_8e8e_a3c6,
+            0xf866_b424_95e6_1984,
+            0x7af5_7b5f_91f1_96fe,
+        ];
+        assert_eq!(state.as_slice(), expected.map(Goldilocks::from_u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "x^5 is not a permutation of the field")]
+    fn checks_alpha() {
+        // 5 divides the order of the multiplicative group of the Goldilocks field
+        Poseidon::<Goldilocks, U12>::new(8, 22, 5);
+    }
+
+    #[test]
+    fn checks_mds() {
+        let poseidon = Poseidon::<Bls12_381Scalar, U3>::new(8, 57, 5);
+        assert!(is_secure(poseidon.mds()));
+        // the first unit vector is an eigenvector of a diagonal matrix
+        let diagonal: Matrix<Bls12_381Scalar, U3> = array_from_fn(|i| {
+            array_from_fn(|j| {
+                if i == j {
+                    Bls12_381Scalar::from_u64(i as u64 + 2)
+                } else {
+                    Bls12_381Scalar::ZERO
+                }
+            })
+        });
+        assert!(!is_secure(&diagonal));
+        // the first unit vector spans the space under this matrix, but not under its square
+        let one = Bls12_381Scalar::ONE;
+        let zero = Bls12_381Scalar::ZERO;
+        let swap: Matrix<_, U3> = Array([
+            Array([zero, one, zero]),
+            Array([one, zero, zero]),
+            Array([zero, zero, one]),
+        ]);
+        assert!(!spans_space(&swap));
+        let cycle: Matrix<_, U3> = Array([
+            Array([zero, zero, one]),
+            Array([one, zero, zero]),
+            Array([zero, one, zero]),
+        ]);
+        assert!(spans_space(&cycle));
+        assert!(!is_secure(&cycle));
+    }
+
+    #[test]
+    fn hashes_with_sponge() {
+        type Sponge = PoseidonSponge<Bls12_381Scalar, U2, U1>;
+        let poseidon = Poseidon::<Bls12_381Scalar, U3>::new(8, 57, 5);
+        let (a, b) = (Bls12_381Scalar::from_u64(1), Bls12_381Scalar::from_u64(2));
+
+        let extra_sponge = ExtraSponge::<Sponge, iopat![Absorb<U2>, Squeeze<U1>]>::start(
+            None,
+            Sponge::new(poseidon.clone()),
+            &mut (),
+        );
+        let (out, _) = extra_sponge
+            .absorb(Array::from_core_array([a, b]), &mut ())
+            .squeeze_array::<U1>(&mut ());
+
+        // the elements are added to the rate, and the tag to the capacity, before a single permutation
+        let tag = tag(&"A2, S1".parse().unwrap(), None);
+        let mut state = Array([a, b, Bls12_381Scalar::from_u128(tag)]);
+        poseidon.permute(&mut state);
+        assert_eq!(out[0], state[0]);
+    }
+}
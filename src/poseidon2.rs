@@ -0,0 +1,348 @@
+//! This module contains the Poseidon2 permutation, over the fields of [`crate::field`], following "Poseidon2: A Faster
+//! Version of the Poseidon Hash Function" (Grassi, Khovratovich, Schofnegger) and its reference implementation.
+//!
+//! Poseidon2 replaces the MDS matrix of Poseidon with two cheaper linear layers: the external matrix of the full rounds
+//! is the all-ones matrix plus the identity, and the internal matrix of the partial rounds is the all-ones matrix plus a
+//! diagonal, so that each of them is applied in a linear number of additions and multiplications. Partial rounds
+//! only add a constant to the first element of the state.
+//!
+//! The round constants are sampled from the Grain LFSR, as those of [`crate::poseidon`] are, one per element in full
+//! rounds and a single one in partial rounds. Only the widths 2 and 3 are supported: their internal matrices are fixed
+//! by the specification, while those of larger widths are drawn at random by the reference implementation.
+//! [`Poseidon2Sponge`] is the SAFE sponge over the permutation, whose single element of capacity holds the tag in
+//! large fields only: the Goldilocks field would need three.
+
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use hybrid_array::{Array, ArraySize};
+use typenum::Sum;
+
+use crate::array_from_fn;
+use crate::field::{is_permutation_exponent, Field};
+use crate::grain::{GrainLfsr, GrainSBox};
+use crate::sponge::{Additive, Permutation, SafeSponge};
+
+/// The SAFE sponge, absorbing in the mode `Mode`, over a Poseidon2 permutation of width `Rate + Capacity`.
+pub type Poseidon2Sponge<F, Rate, Capacity, Mode = Additive> =
+    SafeSponge<Poseidon2<F, Sum<Rate, Capacity>>, Rate, Capacity, Mode>;
+
+/// The Poseidon2 permutation over `F`, with a state of `W` elements, where `W` is 2 or 3.
+/// The state is first multiplied by the external matrix. Full rounds then add their round constants to the state,
+/// apply the S-box `x^alpha` to all of its elements, and multiply it by the external matrix, while partial rounds add
+/// their round constant to the first element, apply the S-box to it, and multiply the state by the internal matrix.
+/// Half of the full rounds come first, followed by the partial rounds, and by the other half of the full rounds.
+pub struct Poseidon2<F, W: ArraySize<F>> {
+    full_rounds: usize,
+    partial_rounds: usize,
+    alpha: u64,
+    // the constants of each full round, in order
+    external_constants: Vec<Array<F, W>>,
+    // the constant of each partial round, in order
+    internal_constants: Vec<F>,
+    // the diagonal of the internal matrix, minus one
+    internal_diagonal: Array<F, W>,
+}
+
+impl<F: Field, W: ArraySize<F>> Poseidon2<F, W> {
+    /// Returns the instance with `full_rounds` full rounds, `partial_rounds` partial rounds, and the S-box
+    /// `x^alpha`, generating its round constants from the Grain LFSR.
+    ///
+    /// This panics if the width is not 2 or 3, if the number of full rounds is odd, or if `x^alpha` is not a
+    /// permutation of the field.
+    pub fn new(full_rounds: usize, partial_rounds: usize, alpha: u64) -> Self {
+        let mut lfsr = GrainLfsr::new(
+            F::MODULUS_BITS,
+            GrainSBox::Power,
+            W::USIZE,
+            full_rounds,
+            partial_rounds,
+        );
+        let half = full_rounds / 2;
+        let mut external_constants = Vec::with_capacity(full_rounds);
+        let mut internal_constants = Vec::with_capacity(partial_rounds);
+        for round in 0..full_rounds + partial_rounds {
+            if round < half || round >= half + partial_rounds {
+                external_constants.push(array_from_fn(|_| lfsr.next_field_element()));
+            } else {
+                internal_constants.push(lfsr.next_field_element());
+            }
+        }
+        Self::from_parameters(
+            full_rounds,
+            partial_rounds,
+            alpha,
+            external_constants,
+            internal_constants,
+        )
+    }
+
+    /// Returns the instance of the given parameters, which are not checked for security.
+    /// There must be one array of round constants per full round, and one round constant per partial round.
+    /// The internal matrix is the one of the specification, `[[2, 1], [1, 3]]` or `[[2, 1, 1], [1, 2, 1], [1, 1, 3]]`.
+    ///
+    /// This panics if the width is not 2 or 3, if the number of full rounds is odd, if `x^alpha` is not a
+    /// permutation of the field, or if the numbers of round constants do not match the numbers of rounds.
+    pub fn from_parameters(
+        full_rounds: usize,
+        partial_rounds: usize,
+        alpha: u64,
+        external_constants: Vec<Array<F, W>>,
+        internal_constants: Vec<F>,
+    ) -> Self {
+        assert!(W::USIZE == 2 || W::USIZE == 3, "the width must be 2 or 3");
+        assert!(
+            full_rounds % 2 == 0,
+            "the number of full rounds must be even"
+        );
+        assert!(
+            is_permutation_exponent::<F>(alpha),
+            "x^{alpha} is not a permutation of the field"
+        );
+        assert_eq!(
+            external_constants.len(),
+            full_rounds,
+            "there must be one array of round constants per full round"
+        );
+        assert_eq!(
+            internal_constants.len(),
+            partial_rounds,
+            "there must be one round constant per partial round"
+        );
+        let internal_diagonal = array_from_fn(|i| {
+            if i == W::USIZE - 1 {
+                F::from_u64(2)
+            } else {
+                F::ONE
+            }
+        });
+        Poseidon2 {
+            full_rounds,
+            partial_rounds,
+            alpha,
+            external_constants,
+            internal_constants,
+            internal_diagonal,
+        }
+    }
+
+    /// Returns the number of full rounds.
+    pub fn full_rounds(&self) -> usize {
+        self.full_rounds
+    }
+
+    /// Returns the number of partial rounds.
+    pub fn partial_rounds(&self) -> usize {
+        self.partial_rounds
+    }
+
+    /// Returns the exponent of the S-box.
+    pub fn alpha(&self) -> u64 {
+        self.alpha
+    }
+
+    /// Returns the round constants of the full rounds, one array per round.
+    pub fn external_constants(&self) -> &[Array<F, W>] {
+        &self.external_constants
+    }
+
+    /// Returns the round constants of the partial rounds, one element per round.
+    pub fn internal_constants(&self) -> &[F] {
+        &self.internal_constants
+    }
+
+    /// Returns the diagonal of the internal matrix, minus one, as the reference implementation gives it.
+    pub fn internal_diagonal(&self) -> &Array<F, W> {
+        &self.internal_diagonal
+    }
+
+    fn full_round(&self, state: &mut Array<F, W>, constants: &Array<F, W>) {
+        for (element, constant) in state.as_mut_slice().iter_mut().zip(constants.as_slice()) {
+            *element = (*element + *constant).pow_u64(self.alpha);
+        }
+        external_layer(state.as_mut_slice());
+    }
+
+    fn partial_round(&self, state: &mut Array<F, W>, constant: F) {
+        state[0] = (state[0] + constant).pow_u64(self.alpha);
+        // the internal matrix is the all-ones matrix plus the diagonal
+        let sum = sum(state.as_slice());
+        for (element, d) in state
+            .as_mut_slice()
+            .iter_mut()
+            .zip(self.internal_diagonal.as_slice())
+        {
+            *element = *element * *d + sum;
+        }
+    }
+}
+
+// Arrays of generic size are neither `Clone` nor `Debug`, so those are implemented element-wise.
+impl<F: Field, W: ArraySize<F>> Clone for Poseidon2<F, W> {
+    fn clone(&self) -> Self {
+        Poseidon2 {
+            full_rounds: self.full_rounds,
+            partial_rounds: self.partial_rounds,
+            alpha: self.alpha,
+            external_constants: self
+                .external_constants
+                .iter()
+                .map(|constants| array_from_fn(|i| constants[i]))
+                .collect(),
+            internal_constants: self.internal_constants.clone(),
+            internal_diagonal: array_from_fn(|i| self.internal_diagonal[i]),
+        }
+    }
+}
+
+// The constants are not displayed, as they are determined by the other parameters for generated instances.
+impl<F: Field, W: ArraySize<F>> fmt::Debug for Poseidon2<F, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Poseidon2")
+            .field("width", &W::USIZE)
+            .field("full_rounds", &self.full_rounds)
+            .field("partial_rounds", &self.partial_rounds)
+            .field("alpha", &self.alpha)
+            .field("internal_diagonal", &self.internal_diagonal.as_slice())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Field, W: ArraySize<F>> Permutation for Poseidon2<F, W> {
+    type Element = F;
+    type Width = W;
+
+    fn permute(&self, state: &mut Array<F, W>) {
+        external_layer(state.as_mut_slice());
+        let (first, last) = self.external_constants.split_at(self.full_rounds / 2);
+        for constants in first {
+            self.full_round(state, constants);
+        }
+        for &constant in &self.internal_constants {
+            self.partial_round(state, constant);
+        }
+        for constants in last {
+            self.full_round(state, constants);
+        }
+    }
+}
+
+fn sum<F: Field>(elements: &[F]) -> F {
+    elements.iter().fold(F::ZERO, |acc, x| acc + *x)
+}
+
+// Multiplies the state by the external matrix, `circ(2, 1)` or `circ(2, 1, 1)`.
+fn external_layer<F: Field>(state: &mut [F]) {
+    let sum = sum(state);
+    for element in state.iter_mut() {
+        *element = *element + sum;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bls12_381Scalar, Goldilocks};
+    use crate::sponge::tag;
+    use crate::traits::{Absorb, Squeeze};
+    use crate::{iopat, ExtraSponge};
+    use hybrid_array::ArrayOps;
+    use typenum::{U1, U2, U3, U4};
+
+    fn bls(limbs: [u64; 4]) -> Bls12_381Scalar {
+        Bls12_381Scalar::from_limbs(limbs).unwrap()
+    }
+
+    // These are the test vectors of the reference implementation, for the instances of widths 2 and 3 over the
+    // scalar field of BLS12-381, with x^5, 8 full rounds, and 56 partial rounds.
+    #[test]
+    fn permutes_bls12_381() {
+        let poseidon2 = Poseidon2::<Bls12_381Scalar, U2>::new(8, 56, 5);
+        let mut state = Array::from_core_array([0, 1].map(Bls12_381Scalar::from_u64));
+        poseidon2.permute(&mut state);
+        assert_eq!(
+            state.0,
+            [
+                bls([
+                    0xe189_fb94_5a15_ce4e,
+                    0xd30f_c3d0_9f16_531f,
+                    0x7b61_d19e_67fa_1b4e,
+                    0x73c4_6dd5_30e2_48a8,
+                ]),
+                bls([
+                    0x4578_2628_91b0_43b4,
+                    0x2a3f_ee32_b9dd_0b5f,
+                    0xd579_3b80_2514_0503,
+                    0x1f0e_305e_e21c_9366,
+                ]),
+            ]
+        );
+
+        let poseidon2 = Poseidon2::<Bls12_381Scalar, U3>::new(8, 56, 5);
+        let mut state = Array::from_core_array([0, 1, 2].map(Bls12_381Scalar::from_u64));
+        poseidon2.permute(&mut state);
+        assert_eq!(
+            state.0,
+            [
+                bls([
+                    0xef3f_d457_61fb_f5f0,
+                    0xca5c_ca56_5053_4e56,
+                    0x8ca7_5ee4_407b_6e26,
+                    0x1b15_2349_b195_0b6a,
+                ]),
+                bls([
+                    0xc5f3_6919_af5b_3891,
+                    0x00bd_0275_868f_09eb,
+                    0xc08a_3210_8437_dc00,
+                    0x4c57_93c8_7d51_bdc2,
+                ]),
+                bls([
+                    0x3b81_25f0_8b50_dc6b,
+                    0x2531_8843_d139_7614,
+                    0xa498_6315_9fe5_ba63,
+                    0x1fc8_ed17_1e67_902c,
+                ]),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiplies_by_external_matrix() {
+        let mut state = [1, 2].map(Goldilocks::from_u64);
+        external_layer(&mut state);
+        assert_eq!(state, [4, 5].map(Goldilocks::from_u64));
+
+        let mut state = [1, 2, 3].map(Goldilocks::from_u64);
+        external_layer(&mut state);
+        assert_eq!(state, [7, 8, 9].map(Goldilocks::from_u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "the width must be 2 or 3")]
+    fn checks_width() {
+        Poseidon2::<Goldilocks, U4>::new(8, 22, 7);
+    }
+
+    #[test]
+    fn hashes_with_sponge() {
+        type Sponge = Poseidon2Sponge<Bls12_381Scalar, U2, U1>;
+        let poseidon2 = Poseidon2::<Bls12_381Scalar, U3>::new(8, 56, 5);
+        let (a, b) = (Bls12_381Scalar::from_u64(1), Bls12_381Scalar::from_u64(2));
+
+        let extra_sponge = ExtraSponge::<Sponge, iopat![Absorb<U2>, Squeeze<U1>]>::start(
+            None,
+            Sponge::new(poseidon2.clone()),
+            &mut (),
+        );
+        let (out, _) = extra_sponge
+            .absorb(Array::from_core_array([a, b]), &mut ())
+            .squeeze_array::<U1>(&mut ());
+
+        // the elements are added to the rate, and the tag to the capacity, before a single permutation
+        let tag = tag(&"A2, S1".parse().unwrap(), None);
+        let mut state = Array::from_core_array([a, b, Bls12_381Scalar::from_u128(tag)]);
+        poseidon2.permute(&mut state);
+        assert_eq!(out[0], state[0]);
+    }
+}
@@ -0,0 +1,425 @@
+//! This module contains the Rescue-Prime permutation, over the fields of [`crate::field`], following "Rescue-Prime: a
+//! Standard Specification (SoK)" (Szepieniec, Ashur, Dhooghe) and its reference implementation.
+//!
+//! Each round of Rescue-Prime is made of two steps, which apply an S-box to all the elements of the state, multiply
+//! it by an MDS matrix, and add round constants to it. The S-box of the first step is `x^alpha`, for the smallest
+//! `alpha` for which it is a permutation of the field, and the one of the second step is its inverse `x^(1/alpha)`.
+//! As both S-boxes are of high degree, few rounds are needed.
+//!
+//! The parameters of an instance are derived from its field, width, capacity and security level, as the reference
+//! implementation does: the number of rounds resists Gröbner basis attacks with a margin of 50%, the MDS matrix is
+//! derived from a Vandermonde matrix in the smallest generator of the field, and the round constants are read from
+//! the output of [`Shake256`] on a description of the instance.
+//! [`RescuePrimeSponge`] is the SAFE sponge over the permutation.
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use core::fmt;
+
+use hybrid_array::{Array, ArraySize};
+use typenum::Sum;
+
+use crate::array_from_fn;
+use crate::field::{group_order_rem, is_permutation_exponent, Field};
+use crate::keccak::Shake256;
+use crate::poseidon::{copy, mul_vec, Matrix};
+use crate::sponge::{Additive, Permutation, SafeSponge};
+
+// The maximal number of rounds the Gröbner basis attacks are considered for, before adding the security margin
+const MAX_ATTACKED_ROUNDS: usize = 24;
+// The minimal number of rounds, before adding the security margin
+const MIN_ROUNDS: usize = 5;
+
+/// The SAFE sponge, absorbing in the mode `Mode`, over a Rescue-Prime permutation of width `Rate + Capacity`.
+pub type RescuePrimeSponge<F, Rate, Capacity, Mode = Additive> =
+    SafeSponge<RescuePrime<F, Sum<Rate, Capacity>>, Rate, Capacity, Mode>;
+
+/// The Rescue-Prime permutation over `F`, with a state of `W` elements.
+/// Each round applies the S-box `x^alpha` to all the elements of the state, multiplies the state by the MDS matrix,
+/// and adds a first array of round constants to it, before doing the same with the S-box `x^(1/alpha)` and a second
+/// array of round constants.
+pub struct RescuePrime<F, W: ArraySize<F> + ArraySize<Array<F, W>>> {
+    alpha: u64,
+    // the inverse of alpha modulo p - 1, as little-endian 64-bit limbs
+    alpha_inverse: Vec<u64>,
+    // the two arrays of constants of each round, in order
+    round_constants: Vec<Array<F, W>>,
+    mds: Matrix<F, W>,
+}
+
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> RescuePrime<F, W> {
+    /// Returns the instance for a sponge of capacity `capacity`, which provides `security_level` bits of security,
+    /// deriving all of its parameters as the reference implementation does.
+    ///
+    /// This panics if the capacity is not below the width.
+    pub fn new(capacity: usize, security_level: u32) -> Self {
+        assert!(
+            capacity < W::USIZE,
+            "the capacity must be below the width {}",
+            W::USIZE
+        );
+        let alpha = (3..)
+            .find(|&alpha| is_permutation_exponent::<F>(alpha))
+            .unwrap();
+        let rounds = number_of_rounds(W::USIZE, capacity, security_level, alpha);
+
+        // the constants are read from the output of SHAKE256 on the description of the instance, in chunks one byte
+        // longer than the modulus, which are little-endian integers reduced modulo the modulus
+        let modulus = decimal(F::MODULUS);
+        let description = format!(
+            "Rescue-XLIX({modulus},{},{capacity},{security_level})",
+            W::USIZE
+        );
+        let mut shake = Shake256::new();
+        shake.update(description.as_bytes());
+        let mut reader = shake.finalize();
+        let mut chunk = vec![0u8; F::MODULUS_BITS.div_ceil(8) as usize + 1];
+        let byte = F::from_u64(256);
+        let round_constants = (0..2 * rounds)
+            .map(|_| {
+                array_from_fn(|_| {
+                    reader.read(&mut chunk);
+                    chunk
+                        .iter()
+                        .rev()
+                        .fold(F::ZERO, |acc, &b| acc * byte + F::from_u64(b as u64))
+                })
+            })
+            .collect();
+
+        Self::from_parameters(alpha, round_constants, mds_matrix())
+    }
+
+    /// Returns the instance of the given parameters, which are not checked for security.
+    /// There must be two arrays of round constants per round.
+    ///
+    /// This panics if `x^alpha` is not a permutation of the field, or if the number of arrays of round constants is
+    /// odd.
+    pub fn from_parameters(
+        alpha: u64,
+        round_constants: Vec<Array<F, W>>,
+        mds: Matrix<F, W>,
+    ) -> Self {
+        assert!(
+            is_permutation_exponent::<F>(alpha),
+            "x^{alpha} is not a permutation of the field"
+        );
+        assert!(
+            round_constants.len() % 2 == 0,
+            "there must be two arrays of round constants per round"
+        );
+        RescuePrime {
+            alpha,
+            alpha_inverse: inverse_exponent::<F>(alpha),
+            round_constants,
+            mds,
+        }
+    }
+
+    /// Returns the number of rounds.
+    pub fn rounds(&self) -> usize {
+        self.round_constants.len() / 2
+    }
+
+    /// Returns the exponent of the S-box of the first step of each round.
+    pub fn alpha(&self) -> u64 {
+        self.alpha
+    }
+
+    /// Returns the round constants, two arrays per round.
+    pub fn round_constants(&self) -> &[Array<F, W>] {
+        &self.round_constants
+    }
+
+    /// Returns the MDS matrix.
+    pub fn mds(&self) -> &Matrix<F, W> {
+        &self.mds
+    }
+
+    fn step(&self, state: &mut Array<F, W>, exp: &[u64], constants: &Array<F, W>) {
+        for element in state.as_mut_slice() {
+            *element = element.pow(exp);
+        }
+        *state = mul_vec(&self.mds, state);
+        for (element, constant) in state.as_mut_slice().iter_mut().zip(constants.as_slice()) {
+            *element = *element + *constant;
+        }
+    }
+}
+
+// Arrays of generic size are neither `Clone` nor `Debug`, so those are implemented element-wise.
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> Clone for RescuePrime<F, W> {
+    fn clone(&self) -> Self {
+        RescuePrime {
+            alpha: self.alpha,
+            alpha_inverse: self.alpha_inverse.clone(),
+            round_constants: self.round_constants.iter().map(copy).collect(),
+            mds: array_from_fn(|i| copy(&self.mds[i])),
+        }
+    }
+}
+
+// The constants are not displayed, as they are determined by the other parameters for generated instances.
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> fmt::Debug for RescuePrime<F, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RescuePrime")
+            .field("width", &W::USIZE)
+            .field("rounds", &self.rounds())
+            .field("alpha", &self.alpha)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>> Permutation for RescuePrime<F, W> {
+    type Element = F;
+    type Width = W;
+
+    fn permute(&self, state: &mut Array<F, W>) {
+        let alpha = [self.alpha];
+        for constants in self.round_constants.chunks_exact(2) {
+            self.step(state, &alpha, &constants[0]);
+            self.step(state, &self.alpha_inverse, &constants[1]);
+        }
+    }
+}
+
+// Returns the inverse of `alpha` modulo the order `p - 1` of the multiplicative group, as little-endian limbs, given
+// `alpha` is coprime with it. This is `(k (p - 1) + 1) / alpha`, for the `k` below `alpha` for which it is an integer.
+fn inverse_exponent<F: Field>(alpha: u64) -> Vec<u64> {
+    let alpha = alpha as u128;
+    let rem = group_order_rem::<F>(alpha as u64) as u128;
+    let k = (1..alpha).find(|k| (k * rem + 1) % alpha == 0).unwrap();
+
+    let mut limbs = Vec::with_capacity(F::MODULUS.len() + 1);
+    let mut carry = 1;
+    for (i, &limb) in F::MODULUS.iter().enumerate() {
+        let limb = if i == 0 { limb - 1 } else { limb };
+        let n = limb as u128 * k + carry;
+        limbs.push(n as u64);
+        carry = n >> 64;
+    }
+    limbs.push(carry as u64);
+    // dividing from the most significant limb
+    let mut rem = 0;
+    for limb in limbs.iter_mut().rev() {
+        let n = (rem << 64) | *limb as u128;
+        *limb = (n / alpha) as u64;
+        rem = n % alpha;
+    }
+    limbs
+}
+
+// Returns the number of rounds of the reference implementation: the smallest number of rounds for which the Gröbner
+// basis attacks cost more than `2^security_level`, at least 5, plus 50%.
+fn number_of_rounds(width: usize, capacity: usize, security_level: u32, alpha: u64) -> usize {
+    let rate = width - capacity;
+    // the costs overflow integers, and floats are precise enough to compare them with the target
+    let target = (0..security_level).fold(1f64, |x, _| 2. * x);
+    let rounds = (1..MAX_ATTACKED_ROUNDS)
+        .find(|&rounds| {
+            let degree = (alpha as usize - 1) * width * (rounds - 1) / 2 + 2;
+            let variables = width * (rounds - 1) + rate;
+            let cost = binomial(variables + degree, variables);
+            cost * cost > target
+        })
+        .unwrap_or(MAX_ATTACKED_ROUNDS);
+    (3 * rounds.max(MIN_ROUNDS)).div_ceil(2)
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    let k = k.min(n - k);
+    (0..k).fold(1f64, |acc, i| acc * (n - i) as f64 / (i + 1) as f64)
+}
+
+// Returns the MDS matrix of the reference implementation: the Vandermonde matrix `g^(i * j)` of size `W x 2W`, for
+// the smallest generator `g` of the field, is brought to its reduced row echelon form `[I | A]`, and the matrix is
+// the transpose of `A`.
+fn mds_matrix<F: Field, W: ArraySize<F> + ArraySize<Array<F, W>>>() -> Matrix<F, W> {
+    let width = W::USIZE;
+    let mut rows: Vec<Vec<F>> = (0..width)
+        .map(|i| {
+            let g = F::GENERATOR.pow_u64(i as u64);
+            let mut power = F::ONE;
+            (0..2 * width)
+                .map(|_| {
+                    let res = power;
+                    power = power * g;
+                    res
+                })
+                .collect()
+        })
+        .collect();
+    for col in 0..width {
+        // the left half is an invertible Vandermonde matrix, as the powers of the generator are distinct
+        let pivot = (col..width).find(|&i| !rows[i][col].is_zero()).unwrap();
+        rows.swap(col, pivot);
+        let inv = rows[col][col].inverse().unwrap();
+        let pivot_row: Vec<F> = rows[col].iter().map(|x| *x * inv).collect();
+        for (i, row) in rows.iter_mut().enumerate() {
+            if i == col {
+                row.clone_from(&pivot_row);
+                continue;
+            }
+            let c = row[col];
+            for (x, y) in row.iter_mut().zip(&pivot_row) {
+                *x = *x - c * *y;
+            }
+        }
+    }
+    array_from_fn(|i| array_from_fn(|j| rows[j][width + i]))
+}
+
+// Returns the decimal digits of the integer of little-endian limbs.
+fn decimal(limbs: &[u64]) -> alloc::string::String {
+    let mut limbs = limbs.to_vec();
+    let mut digits = Vec::new();
+    loop {
+        let mut rem = 0;
+        for limb in limbs.iter_mut().rev() {
+            let n = (rem << 64) | *limb as u128;
+            *limb = (n / 10) as u64;
+            rem = n % 10;
+        }
+        digits.push(char::from(b'0' + rem as u8));
+        if limbs.iter().all(|&limb| limb == 0) {
+            return digits.into_iter().rev().collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bls12_381Scalar, Goldilocks};
+    use crate::sponge::tag;
+    use crate::traits::{Absorb, Squeeze};
+    use crate::{iopat, ExtraSponge};
+    use hybrid_array::ArrayOps;
+    use typenum::{U1, U12, U2, U3, U4, U8};
+
+    // These values are not test vectors of the reference implementation, `rescue_prime.sage`: they were produced by
+    // this implementation, and only pin the parameters, the round constants, and the output of the permutation
+    // against regressions.
+    #[test]
+    fn permutes_goldilocks_regression() {
+        let rescue = RescuePrime::<Goldilocks, U12>::new(4, 128);
+        assert_eq!(rescue.alpha(), 7);
+        assert_eq!(rescue.alpha_inverse, [0x9249_2491_b6db_6db7, 0]);
+        assert_eq!(rescue.rounds(), 8);
+        assert_eq!(
+            rescue.round_constants()[0][0].to_u64(),
+            0xdf4a_7c2a_eaa7_6b43
+        );
+        assert_eq!(
+            rescue.round_constants()[15][11].to_u64(),
+            0x9b81_5d1f_02e9_496d
+        );
+        assert_eq!(rescue.mds()[0][0].to_u64(), 0x1d44_32c2_c62b_8560);
+        assert_eq!(rescue.mds()[0][1].to_u64(), 0x9bc1_1561_d644_0acb);
+
+        let mut state: Array<Goldilocks, U12> = array_from_fn(|i| Goldilocks::from_u64(i as u64));
+        rescue.permute(&mut state);
+        assert_eq!(
+            state.0.map(Goldilocks::to_u64),
+            [
+                0xccd9_4518_a9af_0782,
+                0xf7ae_608e_a330_8620,
+                0xf56d_d53f_ae1f_5876,
+                0x11e7_b12a_edd8_ca86,
+                0x869f_9c3f_93cd_5630,
+                0x6ffe_3731_2e58_ac20,
+                0xac42_b1f8_8aa2_7570,
+                0x312f_6b96_f761_1c8a,
+                0xf8b1_9bd5_1a74_1b7e,
+                0x9d1c_158c_fa1b_7a12,
+                0x62ae_69ae_877e_1e51,
+                0xce62_6415_53ff_e1bc,
+            ]
+        );
+    }
+
+    #[test]
+    fn permutes_bls12_381_regression() {
+        let rescue = RescuePrime::<Bls12_381Scalar, U3>::new(1, 128);
+        assert_eq!(rescue.alpha(), 5);
+        assert_eq!(rescue.rounds(), 14);
+        // the S-boxes of the two steps are inverses of each other
+        let x = Bls12_381Scalar::from_u64(0x5afe);
+        assert_eq!(x.pow_u64(5).pow(&rescue.alpha_inverse), x);
+
+        let mut state = Array::from_core_array([0, 1, 2].map(Bls12_381Scalar::from_u64));
+        rescue.permute(&mut state);
+        assert_eq!(
+            state.0.map(Bls12_381Scalar::to_limbs),
+            [
+                [
+                    0xe090_83cf_0b31_abce,
+                    0x904a_e137_6d61_653d,
+                    0xed95_1411_8392_ede2,
+                    0x2e11_83b4_ae57_1061,
+                ],
+                [
+                    0x7ada_74da_35c7_4b85,
+                    0x3bfe_355e_5947_52c8,
+                    0x5340_3dd4_2999_b19c,
+                    0x38f9_e521_c67c_329a,
+                ],
+                [
+                    0x835e_d5cf_c2d8_440e,
+                    0xc802_4c90_47bb_5c79,
+                    0xd85d_191a_1e52_1c1b,
+                    0x69a1_93e3_c273_4c26,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn derives_parameters() {
+        assert_eq!(decimal(Goldilocks::MODULUS), "18446744069414584321");
+        assert_eq!(decimal(&[0]), "0");
+        // the Gröbner basis attacks are cheaper on narrow states, which need more rounds
+        assert_eq!(number_of_rounds(12, 4, 128, 7), 8);
+        assert_eq!(number_of_rounds(3, 1, 128, 5), 14);
+        assert_eq!(number_of_rounds(2, 1, 1, 3), 8);
+
+        // the MDS matrix has no zero minor of size 1 or 2
+        let mds = RescuePrime::<Goldilocks, U4>::new(2, 128).mds;
+        for (i, j) in (0..4).flat_map(|i| (0..4).map(move |j| (i, j))) {
+            assert!(!mds[i][j].is_zero());
+            for (k, l) in (i + 1..4).flat_map(|k| (j + 1..4).map(move |l| (k, l))) {
+                assert!(!(mds[i][j] * mds[k][l] - mds[i][l] * mds[k][j]).is_zero());
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "the capacity must be below the width 8")]
+    fn checks_capacity() {
+        RescuePrime::<Goldilocks, U8>::new(8, 128);
+    }
+
+    #[test]
+    fn hashes_with_sponge() {
+        type Sponge = RescuePrimeSponge<Bls12_381Scalar, U2, U1>;
+        let rescue = RescuePrime::<Bls12_381Scalar, U3>::new(1, 128);
+        let (a, b) = (Bls12_381Scalar::from_u64(1), Bls12_381Scalar::from_u64(2));
+
+        let extra_sponge = ExtraSponge::<Sponge, iopat![Absorb<U2>, Squeeze<U1>]>::start(
+            None,
+            Sponge::new(rescue.clone()),
+            &mut (),
+        );
+        let (out, _) = extra_sponge
+            .absorb(Array::from_core_array([a, b]), &mut ())
+            .squeeze_array::<U1>(&mut ());
+
+        // the elements are added to the rate, and the tag to the capacity, before a single permutation
+        let tag = tag(&"A2, S1".parse().unwrap(), None);
+        let mut state = Array::from_core_array([a, b, Bls12_381Scalar::from_u128(tag)]);
+        rescue.permute(&mut state);
+        assert_eq!(out[0], state[0]);
+    }
+}
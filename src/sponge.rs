@@ -0,0 +1,1059 @@
+//! This module contains a reference implementation of the SAFE sponge, generic over the permutation it is built on.
+//! Unlike the test sponges of this crate, [`SafeSponge`] follows the spec to the letter: the tag computed from the
+//! IOPattern and domain separator initializes the capacity on `start`, elements are absorbed in and squeezed from the
+//! rate with permutations interleaved whenever it is full, calls are checked against the IOPattern, and `finish`
+//! erases the state, returning an error unless the pattern was followed in full.
+//!
+//! The state holds the `Rate` elements of the rate first, followed by the `Capacity` elements of the capacity.
+//!
+//! [`KeccakByteSponge`] follows the same spec over bytes rather than field elements, for hashing outside of circuits.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Add, IndexMut};
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use hybrid_array::{Array, ArraySize};
+use typenum::{NonZero, Unsigned};
+
+use crate::keccak::{keccak_f1600, Sha3_256};
+use crate::{
+    array_from_fn, array_from_uninit, Error, IOPattern, IntoIter, NativeSpongeAPI, SpongeOp,
+};
+
+/// The elements of the state of a sponge.
+pub trait SpongeElement: Copy + Default {
+    /// Absorbs `input` into this element of the rate, using the group operation of the element type
+    /// (e.g. addition in a field, XOR for bit strings).
+    fn absorb(&mut self, input: Self);
+
+    /// Adds the 128-bit tag of the sponge to the first elements of its capacity.
+    /// Implementations panic if the capacity is too small to hold the tag.
+    fn add_tag(capacity: &mut [Self], tag: u128);
+}
+
+/// 64-bit lanes, as those of Keccak, absorb by XOR, and take the tag over two elements, high bits first.
+impl SpongeElement for u64 {
+    fn absorb(&mut self, input: u64) {
+        *self ^= input;
+    }
+
+    fn add_tag(capacity: &mut [u64], tag: u128) {
+        assert!(
+            capacity.len() >= 2,
+            "the capacity of a sponge over u64 must hold at least 2 elements"
+        );
+        capacity[0] ^= (tag >> 64) as u64;
+        capacity[1] ^= tag as u64;
+    }
+}
+
+/// Bytes absorb by XOR, and take the tag over sixteen elements, in big-endian order.
+impl SpongeElement for u8 {
+    fn absorb(&mut self, input: u8) {
+        *self ^= input;
+    }
+
+    fn add_tag(capacity: &mut [u8], tag: u128) {
+        assert!(
+            capacity.len() >= 16,
+            "the capacity of a sponge over u8 must hold at least 16 elements"
+        );
+        for (element, byte) in capacity.iter_mut().zip(tag.to_be_bytes()) {
+            *element ^= byte;
+        }
+    }
+}
+
+/// A permutation of the state of a sponge, which is all that differs between sponge constructions over different
+/// backends. The width of the state is a type-level number, against which sponges check their rate and capacity at
+/// compile time.
+pub trait Permutation {
+    /// The type of the elements of the state
+    type Element: SpongeElement;
+    /// The number of elements of the state
+    type Width: ArraySize<Self::Element>;
+
+    /// Applies the permutation to `state`, which holds the rate followed by the capacity.
+    fn permute(&self, state: &mut Array<Self::Element, Self::Width>);
+
+    /// Applies the permutation to each of `states`.
+    /// The default implementation permutes them one after the other. Implementations may override it, e.g. to
+    /// interleave the computations over several states.
+    fn permute_batch(&self, states: &mut [Array<Self::Element, Self::Width>]) {
+        for state in states {
+            self.permute(state);
+        }
+    }
+}
+
+/// The way a sponge absorbs elements into its rate, as a type-level marker.
+/// The mode only changes how elements enter the rate: sponges in either mode enforce IOPatterns, compute their tag,
+/// and interleave permutations in the same way.
+///
+/// There is no duplex mode, as both modes already duplex: a squeeze following an absorption permutes the state first,
+/// and an absorption following a squeeze writes from the start of the rate, which that permutation has refreshed.
+/// An IOPattern alternating absorptions and squeezes of at most `Rate` elements, such as `A3, S2, A4, S4`, thus makes
+/// one duplexing call of Bertoni et al. per pair of words, in [`Additive`] mode for the duplex construction and in
+/// [`Overwrite`] mode for the overwrite duplex. Inputs are not padded, as the tag commits to their lengths.
+pub trait AbsorptionMode {
+    /// Absorbs `input` into `element`, an element of the rate.
+    fn absorb<E: SpongeElement>(element: &mut E, input: E);
+}
+
+/// Elements are absorbed by adding them to the rate, with the group operation of their type (see
+/// [`SpongeElement::absorb`]). This is how the SAFE spec, the sponge and duplex constructions of Bertoni et al. (as
+/// FIPS 202 instantiates them), and Neptune absorb elements.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Additive;
+
+impl AbsorptionMode for Additive {
+    fn absorb<E: SpongeElement>(element: &mut E, input: E) {
+        element.absorb(input);
+    }
+}
+
+/// Elements are absorbed by overwriting the rate, as in the overwrite mode of Bertoni et al. The absorbed elements
+/// then no longer depend on the previous state of the rate, which the capacity still carries.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Overwrite;
+
+impl AbsorptionMode for Overwrite {
+    fn absorb<E: SpongeElement>(element: &mut E, input: E) {
+        *element = input;
+    }
+}
+
+/// Returns the tag of a sponge instance, as the SAFE spec computes it from its IOPattern and domain separator.
+/// The pattern is normalized, each of its words is encoded as a big-endian 32-bit integer, with the most significant
+/// bit set for absorptions, and the domain separator, if any, is appended in the same encoding.
+/// The tag is the first 128 bits of the SHA3-256 hash of this encoding.
+///
+/// This panics if a word of the normalized pattern counts `2^31` elements or more, as it would not fit the encoding.
+pub fn tag(pattern: &IOPattern, domain_separator: Option<u32>) -> u128 {
+    let mut hasher = Sha3_256::new();
+    let mut encode = |op: SpongeOp| {
+        let word = match op {
+            SpongeOp::Absorb(n) if n < 1 << 31 => n | 1 << 31,
+            SpongeOp::Squeeze(n) if n < 1 << 31 => n,
+            _ => panic!("the word {op} is too large to be encoded in the tag"),
+        };
+        hasher.update(&word.to_be_bytes());
+    };
+
+    let mut word: Option<SpongeOp> = None;
+    for op in pattern {
+        match (&mut word, op) {
+            (_, SpongeOp::Absorb(0) | SpongeOp::Squeeze(0)) => (),
+            (Some(SpongeOp::Absorb(n)), SpongeOp::Absorb(m))
+            | (Some(SpongeOp::Squeeze(n)), SpongeOp::Squeeze(m)) => {
+                *n = n
+                    .checked_add(m)
+                    .unwrap_or_else(|| panic!("the words of {pattern} overflow once merged"))
+            }
+            _ => {
+                if let Some(word) = word.replace(op) {
+                    encode(word);
+                }
+            }
+        }
+    }
+    if let Some(word) = word {
+        encode(word);
+    }
+    if let Some(domain_separator) = domain_separator {
+        hasher.update(&domain_separator.to_be_bytes());
+    }
+
+    let hash = hasher.finalize();
+    let mut res = [0u8; 16];
+    res.copy_from_slice(&hash[..16]);
+    u128::from_be_bytes(res)
+}
+
+// The progress of a sponge through its IOPattern. Successive calls of the same kind aggregate, and words with a zero
+// count are skipped, so that the sponge follows the normal form of the pattern.
+#[derive(Clone, Debug)]
+struct Progress {
+    ops: IntoIter,
+    // what remains of the word in use, if any
+    current: Option<SpongeOp>,
+}
+
+impl Progress {
+    fn new(pattern: IOPattern) -> Self {
+        Progress {
+            ops: pattern.into_iter(),
+            current: None,
+        }
+    }
+
+    fn next_word(&mut self) -> Option<SpongeOp> {
+        self.current.take().or_else(|| {
+            self.ops
+                .find(|op| !matches!(op, SpongeOp::Absorb(0) | SpongeOp::Squeeze(0)))
+        })
+    }
+
+    // Records the operation `op`, and panics if the pattern does not allow it.
+    fn record(&mut self, op: SpongeOp) {
+        let (mut remaining, absorb) = match op {
+            SpongeOp::Absorb(n) => (n, true),
+            SpongeOp::Squeeze(n) => (n, false),
+        };
+        while remaining > 0 {
+            let word = self.next_word();
+            let available = match (word, absorb) {
+                (Some(SpongeOp::Absorb(n)), true) | (Some(SpongeOp::Squeeze(n)), false) => n,
+                _ => {
+                    let err = Error::UnexpectedOperation {
+                        expected: word,
+                        actual: op,
+                    };
+                    panic!("SpongeAPI invariant violated: {err}");
+                }
+            };
+            let used = available.min(remaining);
+            remaining -= used;
+            if available > used {
+                self.current = Some(if absorb {
+                    SpongeOp::Absorb(available - used)
+                } else {
+                    SpongeOp::Squeeze(available - used)
+                });
+            }
+        }
+    }
+
+    fn is_complete(&mut self) -> bool {
+        self.next_word().is_none()
+    }
+}
+
+// Overwrites the state with default elements, in a way the compiler does not elide.
+fn erase<E: SpongeElement>(state: &mut [E]) {
+    for element in state {
+        // Safety: the pointer comes from a mutable reference, so it is valid and aligned
+        unsafe { core::ptr::write_volatile(element, E::default()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+// The position of a sponge in its rate and in its IOPattern. The sponges of this module only differ in the storage of
+// their state and in their permutation, and walk their rate in the same way.
+#[derive(Debug)]
+struct Cursor {
+    // the positions in the rate of the next element to absorb, and of the next element to squeeze
+    absorb_pos: usize,
+    squeeze_pos: usize,
+    // `None` when the sponge is not started
+    progress: Option<Progress>,
+}
+
+impl Cursor {
+    fn new(rate: usize) -> Self {
+        Cursor {
+            absorb_pos: 0,
+            squeeze_pos: rate,
+            progress: None,
+        }
+    }
+
+    fn progress(&mut self) -> &mut Progress {
+        self.progress
+            .as_mut()
+            .expect("SpongeAPI invariant violated: the sponge is used before start")
+    }
+
+    // Absorbs `elements` into the `rate` first elements of `state` following `Mode`, applying `permute` to the state
+    // whenever the rate is full.
+    fn absorb<Mode, E, S>(
+        &mut self,
+        state: &mut S,
+        rate: usize,
+        length: u32,
+        elements: &[E],
+        mut permute: impl FnMut(&mut S),
+    ) where
+        Mode: AbsorptionMode,
+        E: SpongeElement,
+        S: IndexMut<usize, Output = E>,
+    {
+        assert_eq!(length as usize, elements.len());
+        self.progress().record(SpongeOp::Absorb(length));
+        for &element in elements {
+            if self.absorb_pos == rate {
+                permute(state);
+                self.absorb_pos = 0;
+            }
+            Mode::absorb(&mut state[self.absorb_pos], element);
+            self.absorb_pos += 1;
+        }
+        // the next squeeze permutes the state first, so that its output depends on the absorbed elements
+        if length > 0 {
+            self.squeeze_pos = rate;
+        }
+    }
+
+    // Squeezes `length` elements from the `rate` first elements of `state`, passing each of them to `out` along with
+    // its index, and applying `permute` to the state whenever the rate is exhausted.
+    fn squeeze_with<E, S>(
+        &mut self,
+        state: &mut S,
+        rate: usize,
+        length: u32,
+        mut permute: impl FnMut(&mut S),
+        mut out: impl FnMut(usize, E),
+    ) where
+        E: SpongeElement,
+        S: IndexMut<usize, Output = E>,
+    {
+        self.progress().record(SpongeOp::Squeeze(length));
+        for i in 0..length as usize {
+            if self.squeeze_pos == rate {
+                permute(state);
+                self.squeeze_pos = 0;
+                self.absorb_pos = 0;
+            }
+            out(i, state[self.squeeze_pos]);
+            self.squeeze_pos += 1;
+        }
+    }
+
+    fn reset(&mut self, rate: usize) {
+        self.absorb_pos = 0;
+        self.squeeze_pos = rate;
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        match self
+            .progress
+            .take()
+            .map(|mut progress| progress.is_complete())
+        {
+            Some(true) => Ok(()),
+            _ => Err(Error::ParameterUsageMismatch),
+        }
+    }
+}
+
+/// A sponge implementing the SAFE spec over the permutation `P`, with a state of `Rate + Capacity` elements.
+/// The width of the permutation must be `Rate + Capacity`, which is checked at compile time, and the rate must not be
+/// empty. Elements are absorbed into the rate following `Mode`, by addition by default, see [`AbsorptionMode`].
+///
+/// The sponge must be started before use, and each instance can be started again after it finishes.
+/// As the SpongeAPI does not let `absorb` and `squeeze` return errors, calls which do not follow the IOPattern
+/// panic, as do calls to either before `start`.
+pub struct SafeSponge<P: Permutation, Rate, Capacity, Mode = Additive>
+where
+    Rate: Add<Capacity, Output = P::Width>,
+{
+    permutation: P,
+    state: Array<P::Element, P::Width>,
+    cursor: Cursor,
+    _parameters: PhantomData<(Rate, Capacity, Mode)>,
+}
+
+// The state is not displayed, as it may be secret.
+impl<P, Rate, Capacity, Mode> fmt::Debug for SafeSponge<P, Rate, Capacity, Mode>
+where
+    P: Permutation + fmt::Debug,
+    Rate: Add<Capacity, Output = P::Width>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SafeSponge")
+            .field("permutation", &self.permutation)
+            .field("progress", &self.cursor.progress)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<P, Rate, Capacity, Mode> Default for SafeSponge<P, Rate, Capacity, Mode>
+where
+    P: Permutation + Default,
+    Rate: Add<Capacity, Output = P::Width> + Unsigned + NonZero,
+    Mode: AbsorptionMode,
+{
+    fn default() -> Self {
+        Self::new(P::default())
+    }
+}
+
+impl<P, Rate, Capacity, Mode> SafeSponge<P, Rate, Capacity, Mode>
+where
+    P: Permutation,
+    Rate: Add<Capacity, Output = P::Width> + Unsigned + NonZero,
+    Mode: AbsorptionMode,
+{
+    /// Returns a sponge over `permutation`, which needs to be started before use.
+    pub fn new(permutation: P) -> Self {
+        SafeSponge {
+            permutation,
+            state: array_from_fn(|_| P::Element::default()),
+            cursor: Cursor::new(Rate::USIZE),
+            _parameters: PhantomData,
+        }
+    }
+
+    // Squeezes `length` elements, passing each of them to `out` along with its index.
+    fn squeeze_with(&mut self, length: u32, out: impl FnMut(usize, P::Element)) {
+        let permutation = &self.permutation;
+        self.cursor.squeeze_with(
+            &mut self.state,
+            Rate::USIZE,
+            length,
+            |state| permutation.permute(state),
+            out,
+        );
+    }
+
+    fn erase(&mut self) {
+        erase(self.state.as_mut_slice());
+        self.cursor.reset(Rate::USIZE);
+    }
+}
+
+impl<P, Rate, Capacity, Mode> NativeSpongeAPI for SafeSponge<P, Rate, Capacity, Mode>
+where
+    P: Permutation,
+    Rate: Add<Capacity, Output = P::Width> + Unsigned + NonZero,
+    Mode: AbsorptionMode,
+{
+    type Value = P::Element;
+
+    fn start(&mut self, p: IOPattern, domain_separator: Option<u32>) {
+        let tag = tag(&p, domain_separator);
+        self.erase();
+        P::Element::add_tag(&mut self.state[Rate::USIZE..], tag);
+        self.cursor.progress = Some(Progress::new(p));
+    }
+
+    fn absorb(&mut self, length: u32, elements: &[P::Element]) {
+        let permutation = &self.permutation;
+        self.cursor
+            .absorb::<Mode, _, _>(&mut self.state, Rate::USIZE, length, elements, |state| {
+                permutation.permute(state)
+            });
+    }
+
+    fn squeeze(&mut self, length: u32, elements: &mut [P::Element]) {
+        assert_eq!(length as usize, elements.len());
+        self.squeeze_with(length, |i, element| elements[i] = element);
+    }
+
+    // The elements are written in uninitialized storage, rather than over default ones.
+    fn squeeze_array<U: ArraySize<P::Element>>(&mut self) -> Array<P::Element, U> {
+        // Safety: `squeeze_with` passes every index below `U` to the closure, which writes the element at that index
+        unsafe {
+            array_from_uninit(|elements| {
+                self.squeeze_with(U::to_u32(), |i, element| {
+                    elements[i].write(element);
+                })
+            })
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.erase();
+        self.cursor.finish()
+    }
+}
+
+/// The state is erased when the sponge is dropped, whether it finished or not.
+impl<P, Rate, Capacity, Mode> Drop for SafeSponge<P, Rate, Capacity, Mode>
+where
+    P: Permutation,
+    Rate: Add<Capacity, Output = P::Width>,
+{
+    fn drop(&mut self) {
+        erase(self.state.as_mut_slice());
+    }
+}
+
+// The number of bytes of the Keccak-f[1600] state, and of the rate of `KeccakByteSponge`
+const KECCAK_WIDTH: usize = 200;
+const KECCAK_BYTE_RATE: usize = 136;
+
+/// A sponge implementing the SAFE spec over the bytes of the Keccak-f\[1600\] state, for hashing outside of circuits
+/// with the IOPatterns, and the [`crate::ExtraSponge`] typed patterns, of sponges over field elements.
+/// Its rate of 136 bytes and capacity of 64 bytes are those of SHAKE256, and the bytes of the state are those of its
+/// lanes in little-endian order, as in FIPS 202. The tag is added to the first 16 bytes of the capacity.
+///
+/// It behaves as [`SafeSponge`] does in every other respect: it must be started before use, calls which do not
+/// follow the IOPattern panic, and the state is erased when it finishes or is dropped. It is not an instance of
+/// [`SafeSponge`] only because `hybrid-array` has no arrays of the 200 bytes of its state.
+pub struct KeccakByteSponge {
+    state: [u8; KECCAK_WIDTH],
+    cursor: Cursor,
+}
+
+// The state is not displayed, as it may be secret.
+impl fmt::Debug for KeccakByteSponge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeccakByteSponge")
+            .field("progress", &self.cursor.progress)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for KeccakByteSponge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeccakByteSponge {
+    /// Returns a sponge which needs to be started before use.
+    pub fn new() -> Self {
+        KeccakByteSponge {
+            state: [0; KECCAK_WIDTH],
+            cursor: Cursor::new(KECCAK_BYTE_RATE),
+        }
+    }
+
+    fn erase(&mut self) {
+        erase(&mut self.state);
+        self.cursor.reset(KECCAK_BYTE_RATE);
+    }
+}
+
+// Applies Keccak-f[1600] to the lanes of which `state` holds the bytes.
+fn permute_bytes(state: &mut [u8; KECCAK_WIDTH]) {
+    let mut lanes = [0u64; 25];
+    for (lane, bytes) in lanes.iter_mut().zip(state.chunks_exact(8)) {
+        *lane = u64::from_le_bytes(bytes.try_into().unwrap());
+    }
+    keccak_f1600(&mut lanes);
+    for (bytes, lane) in state.chunks_exact_mut(8).zip(&lanes) {
+        bytes.copy_from_slice(&lane.to_le_bytes());
+    }
+    erase(&mut lanes);
+}
+
+impl NativeSpongeAPI for KeccakByteSponge {
+    type Value = u8;
+
+    fn start(&mut self, p: IOPattern, domain_separator: Option<u32>) {
+        let tag = tag(&p, domain_separator);
+        self.erase();
+        u8::add_tag(&mut self.state[KECCAK_BYTE_RATE..], tag);
+        self.cursor.progress = Some(Progress::new(p));
+    }
+
+    fn absorb(&mut self, length: u32, elements: &[u8]) {
+        self.cursor.absorb::<Additive, _, _>(
+            &mut self.state,
+            KECCAK_BYTE_RATE,
+            length,
+            elements,
+            permute_bytes,
+        );
+    }
+
+    fn squeeze(&mut self, length: u32, elements: &mut [u8]) {
+        assert_eq!(length as usize, elements.len());
+        self.cursor.squeeze_with(
+            &mut self.state,
+            KECCAK_BYTE_RATE,
+            length,
+            permute_bytes,
+            |i, element| elements[i] = element,
+        );
+    }
+
+    // The bytes are written in uninitialized storage, rather than over default ones.
+    fn squeeze_array<U: ArraySize<u8>>(&mut self) -> Array<u8, U> {
+        // Safety: `squeeze_with` passes every index below `U` to the closure, which writes the byte at that index
+        unsafe {
+            array_from_uninit(|elements| {
+                self.cursor.squeeze_with(
+                    &mut self.state,
+                    KECCAK_BYTE_RATE,
+                    U::to_u32(),
+                    permute_bytes,
+                    |i, element| {
+                        elements[i].write(element);
+                    },
+                )
+            })
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.erase();
+        self.cursor.finish()
+    }
+}
+
+/// The state is erased when the sponge is dropped, whether it finished or not.
+impl Drop for KeccakByteSponge {
+    fn drop(&mut self) {
+        erase(&mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::{Bls12_381Scalar, Field, Goldilocks};
+    use crate::keccak::KeccakF1600;
+    use crate::poseidon::{Poseidon, PoseidonSponge};
+    use crate::SpongeAPI;
+    use hybrid_array::ArrayOps;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::prelude::rust_2021::*;
+    use std::{format, vec};
+    use typenum::{U1, U12, U17, U2, U3, U4, U5, U8};
+
+    // The identity over a state of `W` field elements, which makes the effect of the sponge on its state visible
+    #[derive(Debug, Default)]
+    struct Identity<F, W>(PhantomData<(F, W)>);
+
+    impl<F: Field, W: ArraySize<F>> Permutation for Identity<F, W> {
+        type Element = F;
+        type Width = W;
+
+        fn permute(&self, _state: &mut Array<F, W>) {}
+    }
+
+    // 17 lanes of rate, as SHA3-256 uses
+    type KeccakSponge = SafeSponge<KeccakF1600, U17, U8>;
+
+    fn pat(s: &str) -> IOPattern {
+        s.parse().unwrap()
+    }
+
+    // Runs `sponge` over `pattern`, absorbing the elements `input(0)`, `input(1)`, ... in order, and returns the
+    // squeezed elements
+    fn drive<S: NativeSpongeAPI>(
+        sponge: &mut S,
+        pattern: &str,
+        domain_separator: Option<u32>,
+        input: impl Fn(usize) -> S::Value,
+    ) -> Vec<S::Value>
+    where
+        S::Value: Default + Clone,
+    {
+        let pattern = pat(pattern);
+        let (mut absorbed, mut squeezed) = (0, vec![]);
+        sponge.start(pattern.clone(), domain_separator);
+        for op in &pattern {
+            match op {
+                SpongeOp::Absorb(n) => {
+                    let elements: Vec<S::Value> =
+                        (absorbed..absorbed + n as usize).map(&input).collect();
+                    sponge.absorb(n, &elements);
+                    absorbed += n as usize;
+                }
+                SpongeOp::Squeeze(n) => {
+                    let mut elements = vec![S::Value::default(); n as usize];
+                    sponge.squeeze(n, &mut elements);
+                    squeezed.extend(elements);
+                }
+            }
+        }
+        sponge.finish().unwrap();
+        squeezed
+    }
+
+    // Runs the sponge over `pattern`, absorbing the elements 0, 1, ... in order, and returns the squeezed elements
+    fn run(pattern: &str, domain_separator: Option<u32>) -> Vec<u64> {
+        drive(
+            &mut KeccakSponge::default(),
+            pattern,
+            domain_separator,
+            |i| i as u64,
+        )
+    }
+
+    #[test]
+    fn permutes_batches() {
+        let mut states = [
+            Array::from_core_array([0u64; 25]),
+            Array::from_core_array([1u64; 25]),
+        ];
+        let mut expected = states;
+        for state in expected.iter_mut() {
+            KeccakF1600.permute(state);
+        }
+        KeccakF1600.permute_batch(&mut states);
+        assert_eq!(states, expected);
+    }
+
+    #[test]
+    fn tags() {
+        // the tag only depends on the normal form of the pattern
+        assert_eq!(tag(&pat("A2, A3, S0, S1"), None), tag(&pat("A5, S1"), None));
+        assert_ne!(tag(&pat("A5, S1"), None), tag(&pat("A5, S1"), Some(0)));
+        assert_ne!(tag(&pat("A5, S1"), None), tag(&pat("S1, A5"), None));
+        assert_eq!(
+            tag(&pat("A5, S1"), None),
+            0x3927_aa37_1664_1575_e6c6_557c_1a05_ee74
+        );
+        assert_eq!(
+            tag(&pat("A5, S1"), Some(42)),
+            0x795b_d436_7aa4_fa58_4c74_d1d3_fb40_929f
+        );
+    }
+
+    // These values are computed by an independent implementation of the spec over Keccak-f[1600]
+    #[test]
+    fn squeezes() {
+        assert_eq!(run("A5, S1", None), [0x713c_f9af_eb4b_389a]);
+        // over several permutations
+        let squeezed = run("A20, S20, A3, S2", Some(42));
+        assert_eq!(squeezed.len(), 22);
+        assert_eq!(
+            squeezed[..2],
+            [0xd93d_fd18_2173_647b, 0x04fa_f427_0408_c3d7]
+        );
+        assert_eq!(
+            squeezed[16..18],
+            [0x0eb9_33a9_d41d_f29b, 0xf2db_95b2_5d6c_bb35]
+        );
+        assert_eq!(
+            squeezed[20..],
+            [0x7cd2_6133_4adf_b981, 0x8c2f_1116_64d3_c6b7]
+        );
+    }
+
+    #[test]
+    fn aggregates_calls() {
+        let pattern = pat("A20, S20");
+        let elements: Vec<u64> = (0..20).collect();
+        let mut sponge = KeccakSponge::default();
+        let mut squeezed = [0; 20];
+        NativeSpongeAPI::start(&mut sponge, pattern, None);
+        sponge.absorb_parts(
+            &[&elements[..3], &[], &elements[3..17], &elements[17..]],
+            &mut (),
+        );
+        let (first, second) = squeezed.split_at_mut(17);
+        sponge.squeeze_parts(&mut [first, second], &mut ());
+        NativeSpongeAPI::finish(&mut sponge).unwrap();
+        assert_eq!(squeezed.to_vec(), run("A20, S20", None));
+    }
+
+    #[test]
+    fn separates_domains() {
+        assert_ne!(run("A5, S4", None), run("A5, S4", Some(1)));
+        assert_ne!(run("A5, S4", Some(1)), run("A5, S4", Some(2)));
+        // the pattern is part of the tag, even where the outputs would otherwise coincide
+        assert_ne!(run("A5, S4", None)[..2], run("A5, S2", None)[..]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected operation Squeeze(1), expected Absorb(2)")]
+    fn enforces_pattern() {
+        let mut sponge = KeccakSponge::default();
+        NativeSpongeAPI::start(&mut sponge, pat("A5, S1"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 3, &[1, 2, 3]);
+        NativeSpongeAPI::squeeze(&mut sponge, 1, &mut [0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected operation Absorb(1), expected the end of the pattern")]
+    fn enforces_pattern_end() {
+        let mut sponge = KeccakSponge::default();
+        NativeSpongeAPI::start(&mut sponge, pat("S1"), None);
+        NativeSpongeAPI::squeeze(&mut sponge, 1, &mut [0]);
+        NativeSpongeAPI::absorb(&mut sponge, 1, &[1]);
+    }
+
+    // Runs the calls of `calls` on a sponge of each mode, and checks that they panic, or finish, in the same way
+    fn check_same_enforcement(
+        pattern: &str,
+        calls: impl Fn(&mut dyn NativeSpongeAPI<Value = u64>) -> Result<(), Error>,
+    ) {
+        let run = |sponge: &mut dyn NativeSpongeAPI<Value = u64>| {
+            sponge.start(pat(pattern), None);
+            catch_unwind(AssertUnwindSafe(|| calls(sponge))).map_err(|payload| {
+                payload
+                    .downcast_ref::<String>()
+                    .expect("the sponge panics with a message")
+                    .clone()
+            })
+        };
+        let additive = run(&mut SafeSponge::<KeccakF1600, U17, U8, Additive>::default());
+        let overwrite = run(&mut SafeSponge::<KeccakF1600, U17, U8, Overwrite>::default());
+        assert_eq!(format!("{additive:?}"), format!("{overwrite:?}"));
+    }
+
+    #[test]
+    fn enforces_pattern_in_all_modes() {
+        check_same_enforcement("A20, S2", |sponge| {
+            sponge.absorb(20, &[1; 20]);
+            sponge.squeeze(2, &mut [0; 2]);
+            sponge.finish()
+        });
+        check_same_enforcement("A5, S1", |sponge| {
+            sponge.absorb(3, &[1, 2, 3]);
+            sponge.squeeze(1, &mut [0]);
+            sponge.finish()
+        });
+        check_same_enforcement("A5, S1", |sponge| {
+            sponge.absorb(5, &[1; 5]);
+            sponge.finish()
+        });
+        check_same_enforcement("S1", |sponge| {
+            sponge.squeeze(1, &mut [0]);
+            sponge.absorb(1, &[1]);
+            sponge.finish()
+        });
+    }
+
+    #[test]
+    fn absorbs_by_mode() {
+        // with the identity as permutation, the rate shows how the third element is absorbed over the first one
+        fn squeeze<Mode: AbsorptionMode>() -> Vec<Goldilocks> {
+            let mut sponge = SafeSponge::<Identity<Goldilocks, U5>, U2, U3, Mode>::default();
+            NativeSpongeAPI::start(&mut sponge, pat("A3, S2"), None);
+            NativeSpongeAPI::absorb(&mut sponge, 3, &[1, 2, 3].map(Goldilocks::from_u64));
+            let mut out = [Goldilocks::ZERO; 2];
+            NativeSpongeAPI::squeeze(&mut sponge, 2, &mut out);
+            NativeSpongeAPI::finish(&mut sponge).unwrap();
+            out.to_vec()
+        }
+        assert_eq!(squeeze::<Additive>(), [4, 2].map(Goldilocks::from_u64));
+        assert_eq!(squeeze::<Overwrite>(), [3, 2].map(Goldilocks::from_u64));
+
+        // the outputs differ as soon as a permutation separates absorptions into the same position
+        let run = |sponge: &mut dyn NativeSpongeAPI<Value = u64>| {
+            let mut out = [0; 2];
+            sponge.start(pat("A20, S2"), None);
+            sponge.absorb(20, &[1; 20]);
+            sponge.squeeze(2, &mut out);
+            sponge.finish().unwrap();
+            out
+        };
+        let additive = run(&mut SafeSponge::<KeccakF1600, U17, U8, Additive>::default());
+        let overwrite = run(&mut SafeSponge::<KeccakF1600, U17, U8, Overwrite>::default());
+        assert_ne!(additive, overwrite);
+        // while a single block of input is absorbed in the same way in both modes, into the erased rate
+        let run = |sponge: &mut dyn NativeSpongeAPI<Value = u64>| {
+            let mut out = [0; 2];
+            sponge.start(pat("A17, S2"), None);
+            sponge.absorb(17, &[1; 17]);
+            sponge.squeeze(2, &mut out);
+            sponge.finish().unwrap();
+            out
+        };
+        assert_eq!(
+            run(&mut SafeSponge::<KeccakF1600, U17, U8, Additive>::default()),
+            run(&mut SafeSponge::<KeccakF1600, U17, U8, Overwrite>::default())
+        );
+    }
+
+    // Over Keccak lanes, the additive mode XORs each block of input into the rate before permuting, as spelled out
+    // here on the state. This checks the block schedule of the sponge, not its outputs against those of FIPS 202,
+    // whose capacity starts from zeros rather than from the tag.
+    #[test]
+    fn additive_mode_xors_blocks_into_the_rate() {
+        let input: Vec<u64> = (0..34).collect();
+        let tag = tag(&pat("A34, S2"), None);
+
+        let mut state = [0u64; 25];
+        state[17] = (tag >> 64) as u64;
+        state[18] = tag as u64;
+        for block in input.chunks(17) {
+            for (lane, x) in state.iter_mut().zip(block) {
+                *lane ^= x;
+            }
+            crate::keccak::keccak_f1600(&mut state);
+        }
+
+        let mut sponge = SafeSponge::<KeccakF1600, U17, U8, Additive>::default();
+        let mut out = [0; 2];
+        NativeSpongeAPI::start(&mut sponge, pat("A34, S2"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 34, &input);
+        NativeSpongeAPI::squeeze(&mut sponge, 2, &mut out);
+        assert_eq!(out, state[..2]);
+    }
+
+    // The overwrite mode writes each chunk of input over the rate before permuting, as spelled out here on a state of
+    // 12 Goldilocks elements with a rate of 8 elements. This checks the block schedule of the sponge, not its outputs
+    // against those of another implementation.
+    #[test]
+    fn overwrite_mode_overwrites_the_rate() {
+        let poseidon = Poseidon::<Goldilocks, U12>::new(8, 22, 7);
+        let input: Vec<Goldilocks> = (0..20).map(Goldilocks::from_u64).collect();
+        let tag = tag(&pat("A20, S4"), None);
+
+        let mut state: Array<Goldilocks, U12> = Array::from_core_array([Goldilocks::ZERO; 12]);
+        Goldilocks::add_tag(&mut state[8..], tag);
+        for chunk in input.chunks(8) {
+            state[..chunk.len()].copy_from_slice(chunk);
+            poseidon.permute(&mut state);
+        }
+
+        let mut sponge = PoseidonSponge::<Goldilocks, U8, U4, Overwrite>::new(poseidon);
+        let mut out = [Goldilocks::ZERO; 4];
+        NativeSpongeAPI::start(&mut sponge, pat("A20, S4"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 20, &input);
+        NativeSpongeAPI::squeeze(&mut sponge, 4, &mut out);
+        assert_eq!(out, state[..4]);
+    }
+
+    // Alternating absorptions and squeezes of at most a rate of elements makes a duplexing call per pair, as spelled
+    // out here on Keccak lanes: the input block enters the rate, the state is permuted, and the output is read from
+    // the rate.
+    #[test]
+    fn duplexes_in_both_modes() {
+        fn check<Mode: AbsorptionMode>(enter: fn(&mut u64, u64)) {
+            let blocks: [&[u64]; 2] = [&[1, 2, 3], &[4; 17]];
+            let lengths = [2, 17];
+            let tag = tag(&pat("A3, S2, A17, S17"), None);
+
+            let mut state = [0u64; 25];
+            state[17] = (tag >> 64) as u64;
+            state[18] = tag as u64;
+            let mut expected = vec![];
+            for (block, length) in blocks.iter().zip(lengths) {
+                for (lane, x) in state.iter_mut().zip(*block) {
+                    enter(lane, *x);
+                }
+                crate::keccak::keccak_f1600(&mut state);
+                expected.extend_from_slice(&state[..length]);
+            }
+
+            let mut sponge = SafeSponge::<KeccakF1600, U17, U8, Mode>::default();
+            let mut out = vec![];
+            NativeSpongeAPI::start(&mut sponge, pat("A3, S2, A17, S17"), None);
+            for (block, length) in blocks.iter().zip(lengths) {
+                let mut output = vec![0; length];
+                NativeSpongeAPI::absorb(&mut sponge, block.len() as u32, block);
+                NativeSpongeAPI::squeeze(&mut sponge, length as u32, &mut output);
+                out.extend(output);
+            }
+            NativeSpongeAPI::finish(&mut sponge).unwrap();
+            assert_eq!(out, expected);
+        }
+        check::<Additive>(|lane, x| *lane ^= x);
+        check::<Overwrite>(|lane, x| *lane = x);
+    }
+
+    #[test]
+    #[should_panic(expected = "used before start")]
+    fn needs_start() {
+        let mut sponge = KeccakSponge::default();
+        NativeSpongeAPI::absorb(&mut sponge, 1, &[1]);
+    }
+
+    #[test]
+    fn absorbs_field_elements() {
+        let pattern = pat("A3, S2");
+        let tag = tag(&pattern, None);
+
+        // the tag takes three elements of the capacity in the Goldilocks field
+        let mut sponge = SafeSponge::<Identity<Goldilocks, U5>, U2, U3>::default();
+        let elements = [1, 2, 3].map(Goldilocks::from_u64);
+        let mut squeezed = [Goldilocks::ZERO; 2];
+        NativeSpongeAPI::start(&mut sponge, pattern.clone(), None);
+        NativeSpongeAPI::absorb(&mut sponge, 3, &elements);
+        let mut capacity = [Goldilocks::ZERO; 3];
+        Goldilocks::add_tag(&mut capacity, tag);
+        assert_eq!(sponge.state[2..], capacity);
+        NativeSpongeAPI::squeeze(&mut sponge, 2, &mut squeezed);
+        // the third element is added to the first after the rate is permuted
+        assert_eq!(squeezed, [elements[0] + elements[2], elements[1]]);
+        NativeSpongeAPI::finish(&mut sponge).unwrap();
+
+        // a single element of the capacity holds the tag in the scalar field of BLS12-381
+        let mut sponge = SafeSponge::<Identity<Bls12_381Scalar, U4>, U3, U1>::default();
+        let elements = [5, 6, 7].map(Bls12_381Scalar::from_u64);
+        let mut squeezed = [Bls12_381Scalar::ZERO; 2];
+        NativeSpongeAPI::start(&mut sponge, pattern, None);
+        NativeSpongeAPI::absorb(&mut sponge, 3, &elements);
+        assert_eq!(sponge.state[3], Bls12_381Scalar::from_u128(tag));
+        NativeSpongeAPI::squeeze(&mut sponge, 2, &mut squeezed);
+        assert_eq!(squeezed, elements[..2]);
+        NativeSpongeAPI::finish(&mut sponge).unwrap();
+    }
+
+    #[test]
+    fn finishes() {
+        let mut sponge = KeccakSponge::default();
+        NativeSpongeAPI::start(&mut sponge, pat("A5, S1, S0"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 5, &[1, 2, 3, 4, 5]);
+        assert!(matches!(
+            NativeSpongeAPI::finish(&mut sponge),
+            Err(Error::ParameterUsageMismatch)
+        ));
+        assert!(sponge.state.as_slice().iter().all(|&lane| lane == 0));
+        // a finished sponge can not be finished again, until it is started
+        assert!(NativeSpongeAPI::finish(&mut sponge).is_err());
+
+        NativeSpongeAPI::start(&mut sponge, pat("A5, S1, S0"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 5, &[1, 2, 3, 4, 5]);
+        NativeSpongeAPI::squeeze(&mut sponge, 1, &mut [0]);
+        assert!(sponge.state.as_slice().iter().any(|&lane| lane != 0));
+        NativeSpongeAPI::finish(&mut sponge).unwrap();
+        assert!(sponge.state.as_slice().iter().all(|&lane| lane == 0));
+    }
+
+    // The expected values are computed by an independent implementation of the sponge
+    #[test]
+    fn hashes_bytes() {
+        let mut sponge = KeccakByteSponge::new();
+        let out = drive(&mut sponge, "A5, S16", None, |i| i as u8);
+        assert_eq!(
+            out,
+            [93, 113, 62, 20, 8, 90, 220, 211, 146, 135, 31, 231, 147, 190, 198, 38]
+        );
+        let out = drive(&mut sponge, "A3, S32", None, |i| b"abc"[i]);
+        assert_eq!(
+            out,
+            [
+                110, 218, 35, 104, 120, 41, 174, 210, 116, 252, 145, 218, 50, 64, 134, 22, 134,
+                236, 173, 49, 66, 219, 135, 148, 159, 4, 8, 20, 246, 151, 36, 200
+            ]
+        );
+
+        // the absorption and the squeezing span several permutations of the rate
+        let out = drive(&mut sponge, "A200, S150, A3, S8", Some(42), |i| i as u8);
+        assert_eq!(out[..8], 0xe28c_5051_91ee_1ed7u64.to_be_bytes());
+        assert_eq!(out[136..144], 0xa3d3_4af6_a6b5_c9feu64.to_be_bytes());
+        assert_eq!(out[150..], 0xb9f7_df3b_14d9_88d4u64.to_be_bytes());
+
+        // calls aggregate as the words of the pattern do
+        let out = drive(&mut sponge, "A1, A1, S4, S4, S0", Some(7), |i| i as u8);
+        assert_eq!(out, 0x83ce_2cc6_d592_82d2u64.to_be_bytes());
+    }
+
+    #[test]
+    fn adds_byte_tags() {
+        let tag = 0x0123_4567_89ab_cdef_fedc_ba98_7654_3210;
+        let mut capacity = [0xffu8; 17];
+        u8::add_tag(&mut capacity, tag);
+        let expected: Vec<u8> = tag.to_be_bytes().iter().map(|b| b ^ 0xff).collect();
+        assert_eq!(capacity[..16], expected);
+        assert_eq!(capacity[16], 0xff);
+
+        let mut sponge = KeccakByteSponge::new();
+        NativeSpongeAPI::start(&mut sponge, pat("A1"), None);
+        assert_eq!(
+            sponge.state[KECCAK_BYTE_RATE..KECCAK_BYTE_RATE + 16],
+            super::tag(&pat("A1"), None).to_be_bytes()
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "SpongeAPI invariant violated: unexpected operation Absorb(1), expected Squeeze(1)"
+    )]
+    fn enforces_byte_pattern() {
+        let mut sponge = KeccakByteSponge::new();
+        NativeSpongeAPI::start(&mut sponge, pat("A2, S1"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 2, &[1, 2]);
+        NativeSpongeAPI::absorb(&mut sponge, 1, &[3]);
+    }
+
+    #[test]
+    fn finishes_bytes() {
+        let mut sponge = KeccakByteSponge::new();
+        NativeSpongeAPI::start(&mut sponge, pat("A5, S1"), None);
+        NativeSpongeAPI::absorb(&mut sponge, 5, b"bytes");
+        assert!(NativeSpongeAPI::finish(&mut sponge).is_err());
+        assert!(sponge.state.iter().all(|&byte| byte == 0));
+    }
+}
@@ -2,12 +2,18 @@
 //! The IOPattern type is a type-level HList of IOWords, which are either Absorb or Squeeze.
 //! The main operations are Normalize, which merges successive words of the same type, and Consume, which takes a word and an IOPattern, and checks whether
 //! it is legal to use the operation of this word on the tip of the IOPattern.
+//!
+//! Checking a flat list of words recurses once per word, which caps the length of patterns to the recursion limit of
+//! the compiler (128 by default). Long patterns can instead be written as a tree of concatenations of shorter ones,
+//! see [`Concat`], on which the operations recurse along the height of the tree rather than along the whole pattern.
+//! The `iopat!` macro builds such a tree out of lists of more than 16 words.
 //! This is explained in more detail in [the spec document][1].
 //!
 //! [1]: https://hackmd.io/bHgsH6mMStCVibM_wYvb2w#SAFE-Sponge-API-for-Field-Elements-%E2%80%93-A-Toolbox-for-ZK-Hash-Applications
 
+use core::fmt;
+use core::marker::PhantomData;
 use core::ops::{Add, Sub};
-use std::marker::PhantomData;
 pub use typenum;
 use typenum::{Bit, Diff, Sum, UInt, Unsigned, U0};
 
@@ -22,10 +28,21 @@ pub struct Absorb<N>(PhantomData<N>);
 pub struct Squeeze<N>(PhantomData<N>);
 
 /// Our trait for common treatment of both patterns
-pub trait IOWord: private::Sealed {}
+pub trait IOWord: private::Sealed {
+    /// Writes the word as its term-level counterpart would be displayed, e.g. `Absorb(5)`
+    fn fmt_word(f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
 
-impl<N: Unsigned> IOWord for Absorb<N> {}
-impl<N: Unsigned> IOWord for Squeeze<N> {}
+impl<N: Unsigned> IOWord for Absorb<N> {
+    fn fmt_word(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&crate::SpongeOp::Absorb(N::U32), f)
+    }
+}
+impl<N: Unsigned> IOWord for Squeeze<N> {
+    fn fmt_word(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&crate::SpongeOp::Squeeze(N::U32), f)
+    }
+}
 
 /// Type-level HList, specialized to IOWord
 /// using  a sealed trait
@@ -38,6 +55,9 @@ pub trait List: private::Sealed {
 
     /// THis returns whether the list is empty
     fn is_empty() -> bool;
+
+    /// Writes the words of the list, separated by commas, see [`PatternDisplay`]
+    fn fmt_words(f: &mut fmt::Formatter<'_>) -> fmt::Result;
 }
 
 impl<Item: IOWord, Next: List> List for Cons<Item, Next> {
@@ -49,6 +69,13 @@ impl<Item: IOWord, Next: List> List for Cons<Item, Next> {
     fn is_empty() -> bool {
         false
     }
+    fn fmt_words(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Item::fmt_word(f)?;
+        if !Next::is_empty() {
+            f.write_str(", ")?;
+        }
+        Next::fmt_words(f)
+    }
 }
 
 impl List for Nil {
@@ -58,6 +85,58 @@ impl List for Nil {
     fn is_empty() -> bool {
         true
     }
+    fn fmt_words(_f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Ok(())
+    }
+}
+
+impl<L: List, R: List> List for Concat<L, R> {
+    fn unit() -> Self {
+        Concat {
+            _phantom: PhantomData,
+        }
+    }
+    fn is_empty() -> bool {
+        L::is_empty() && R::is_empty()
+    }
+    fn fmt_words(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        L::fmt_words(f)?;
+        if !L::is_empty() && !R::is_empty() {
+            f.write_str(", ")?;
+        }
+        R::fmt_words(f)
+    }
+}
+
+/// A helper rendering the type-level IOPattern `I` as its term-level counterpart would be,
+/// e.g. `Absorb(5), Squeeze(3)` for `iopat![Absorb<U5>, Squeeze<U3>]`.
+pub struct PatternDisplay<I: List>(PhantomData<I>);
+
+impl<I: List> PatternDisplay<I> {
+    /// Returns the displayable representation of the pattern `I`.
+    pub fn new() -> Self {
+        PatternDisplay(PhantomData)
+    }
+}
+
+impl<I: List> Default for PatternDisplay<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: List> fmt::Display for PatternDisplay<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        I::fmt_words(f)
+    }
+}
+
+impl<I: List> fmt::Debug for PatternDisplay<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        I::fmt_words(f)?;
+        f.write_str("]")
+    }
 }
 
 /// The concrete type constructor for our HList trait
@@ -70,12 +149,27 @@ pub struct Cons<Item, Next: List> {
 #[derive(Debug)]
 pub struct Nil;
 
-/// Convenience helper for creating an instance of List
+/// The concatenation of two IOPatterns, which lets long patterns be written as a tree of shorter ones.
+/// For instance, `Concat<P, P>` where `P = iopat![Absorb<U2>, Squeeze<U1>]` is the pattern
+/// `Absorb(2), Squeeze(1), Absorb(2), Squeeze(1)`.
+/// The normal form of a concatenation is a concatenation of normal forms, see `Normalize`, so that the type-level
+/// operations on the pattern never recurse deeper than the height of the tree and the length of its flat parts.
+#[derive(Debug)]
+pub struct Concat<L: List, R: List> {
+    _phantom: PhantomData<(L, R)>,
+}
+
+/// Convenience helper for creating an instance of List.
+/// Lists of more than 16 words are split into a [`Concat`] of chunks of 16 words, so that long flat patterns type-check
+/// under the default recursion limit of the compiler.
 #[macro_export]
 macro_rules! iopat {
     () => { $crate::traits::Nil };
     (...$rest:ty) => { $rest };
     ($a:ty) => { $crate::iopat![$a,] };
+    ($a0:ty, $a1:ty, $a2:ty, $a3:ty, $a4:ty, $a5:ty, $a6:ty, $a7:ty, $a8:ty, $a9:ty, $a10:ty, $a11:ty, $a12:ty, $a13:ty, $a14:ty, $a15:ty, $($tok:tt)+) => {
+        $crate::traits::Concat<$crate::iopat![$a0, $a1, $a2, $a3, $a4, $a5, $a6, $a7, $a8, $a9, $a10, $a11, $a12, $a13, $a14, $a15], $crate::iopat![$($tok)+]>
+    };
     ($a:ty, $($tok:tt)*) => {
         $crate::traits::Cons<$a, $crate::iopat![$($tok)*]>
     };
@@ -166,10 +260,289 @@ where
     type Output = Norm<Cons<Absorb<UInt<U, B>>, T>>;
 }
 
+// A word followed by a concatenation is the concatenation of that word and the latter
+impl<U: Unsigned, B: Bit, L: List, R: List> Normalize for Cons<Absorb<UInt<U, B>>, Concat<L, R>>
+where
+    Concat<Cons<Absorb<UInt<U, B>>, Nil>, Concat<L, R>>: Normalize,
+{
+    type Output = Norm<Concat<Cons<Absorb<UInt<U, B>>, Nil>, Concat<L, R>>>;
+}
+
+impl<U: Unsigned, B: Bit, L: List, R: List> Normalize for Cons<Squeeze<UInt<U, B>>, Concat<L, R>>
+where
+    Concat<Cons<Squeeze<UInt<U, B>>, Nil>, Concat<L, R>>: Normalize,
+{
+    type Output = Norm<Concat<Cons<Squeeze<UInt<U, B>>, Nil>, Concat<L, R>>>;
+}
+
+// Both sides are normalized independently, only the words at the boundary are merged
+impl<L: Normalize, R: Normalize> Normalize for Concat<L, R>
+where
+    Norm<L>: Fuse<Norm<R>>,
+{
+    type Output = Fused<Norm<L>, Norm<R>>;
+}
+
+/// Normalizing the head of an IOPattern: zero words are removed and words of the same kind are merged until the
+/// first word of the pattern can not be merged with the following one. The rest of the pattern is left as is, so that
+/// this is constant work on the tail of a normalized pattern.
+pub trait HeadNormalize: List {
+    /// The output of the head normalization
+    type Output: List;
+}
+
+/// Convenience trait for projection of HeadNormalize
+pub type HeadNorm<T> = <T as HeadNormalize>::Output;
+
+impl HeadNormalize for Nil {
+    type Output = Nil;
+}
+
+impl<L: HeadNormalize> HeadNormalize for Cons<Absorb<U0>, L> {
+    type Output = HeadNorm<L>;
+}
+
+impl<L: HeadNormalize> HeadNormalize for Cons<Squeeze<U0>, L> {
+    type Output = HeadNorm<L>;
+}
+
+impl<U: Unsigned, B: Bit> HeadNormalize for Cons<Absorb<UInt<U, B>>, Nil> {
+    type Output = Self;
+}
+
+impl<U: Unsigned, B: Bit> HeadNormalize for Cons<Squeeze<UInt<U, B>>, Nil> {
+    type Output = Self;
+}
+
+impl<U: Unsigned, B: Bit, M: Unsigned, T: List> HeadNormalize
+    for Cons<Absorb<UInt<U, B>>, Cons<Absorb<M>, T>>
+where
+    UInt<U, B>: Add<M>,
+    Cons<Absorb<Sum<UInt<U, B>, M>>, T>: HeadNormalize,
+{
+    type Output = HeadNorm<Cons<Absorb<Sum<UInt<U, B>, M>>, T>>;
+}
+
+impl<U: Unsigned, B: Bit, M: Unsigned, T: List> HeadNormalize
+    for Cons<Squeeze<UInt<U, B>>, Cons<Squeeze<M>, T>>
+where
+    UInt<U, B>: Add<M>,
+    Cons<Squeeze<Sum<UInt<U, B>, M>>, T>: HeadNormalize,
+{
+    type Output = HeadNorm<Cons<Squeeze<Sum<UInt<U, B>, M>>, T>>;
+}
+
+// The head is followed by a non-zero word of the other kind: we are done
+impl<U: Unsigned, B: Bit, U2: Unsigned, B2: Bit, T: List> HeadNormalize
+    for Cons<Squeeze<UInt<U, B>>, Cons<Absorb<UInt<U2, B2>>, T>>
+{
+    type Output = Self;
+}
+
+impl<U: Unsigned, B: Bit, U2: Unsigned, B2: Bit, T: List> HeadNormalize
+    for Cons<Absorb<UInt<U, B>>, Cons<Squeeze<UInt<U2, B2>>, T>>
+{
+    type Output = Self;
+}
+
+impl<U: Unsigned, B: Bit, T: List> HeadNormalize for Cons<Squeeze<UInt<U, B>>, Cons<Absorb<U0>, T>>
+where
+    Cons<Squeeze<UInt<U, B>>, T>: HeadNormalize,
+{
+    type Output = HeadNorm<Cons<Squeeze<UInt<U, B>>, T>>;
+}
+
+impl<U: Unsigned, B: Bit, T: List> HeadNormalize for Cons<Absorb<UInt<U, B>>, Cons<Squeeze<U0>, T>>
+where
+    Cons<Absorb<UInt<U, B>>, T>: HeadNormalize,
+{
+    type Output = HeadNorm<Cons<Absorb<UInt<U, B>>, T>>;
+}
+
+// Concatenations are rare enough in head position that we normalize them in full
+impl<U: Unsigned, B: Bit, L: List, R: List> HeadNormalize for Cons<Absorb<UInt<U, B>>, Concat<L, R>>
+where
+    Self: Normalize,
+{
+    type Output = Norm<Self>;
+}
+
+impl<U: Unsigned, B: Bit, L: List, R: List> HeadNormalize
+    for Cons<Squeeze<UInt<U, B>>, Concat<L, R>>
+where
+    Self: Normalize,
+{
+    type Output = Norm<Self>;
+}
+
+impl<L: List, R: List> HeadNormalize for Concat<L, R>
+where
+    Self: Normalize,
+{
+    type Output = Norm<Self>;
+}
+
+/// Concatenating two IOPatterns, without building a `Concat` node when either of them is empty.
+pub trait Join<R: List>: List {
+    /// The output of the concatenation
+    type Output: List;
+}
+
+/// Convenience trait for projection of Join
+pub type Joined<L, R> = <L as Join<R>>::Output;
+
+impl<R: List> Join<R> for Nil {
+    type Output = R;
+}
+
+/// Splitting the first word from a non-empty IOPattern.
+pub trait PopFirst: List {
+    /// The first word of the pattern
+    type First: IOWord;
+    /// The rest of the pattern
+    type Rest: List;
+}
+
+impl<H: IOWord, T: List> PopFirst for Cons<H, T> {
+    type First = H;
+    type Rest = T;
+}
+
+impl<L: PopFirst, R: List> PopFirst for Concat<L, R>
+where
+    L::Rest: Join<R>,
+{
+    type First = L::First;
+    type Rest = Joined<L::Rest, R>;
+}
+
+/// Splitting the last word from a non-empty IOPattern made of flat lists and concatenations of those, such as the
+/// normal forms of patterns.
+pub trait PopLast: List {
+    /// The last word of the pattern
+    type Last: IOWord;
+    /// The rest of the pattern
+    type Init: List;
+}
+
+impl<H: IOWord> PopLast for Cons<H, Nil> {
+    type Last = H;
+    type Init = Nil;
+}
+
+impl<H: IOWord, H2: IOWord, T: List> PopLast for Cons<H, Cons<H2, T>>
+where
+    Cons<H2, T>: PopLast,
+{
+    type Last = <Cons<H2, T> as PopLast>::Last;
+    type Init = Cons<H, <Cons<H2, T> as PopLast>::Init>;
+}
+
+impl<L: Join<R::Init>, R: PopLast> PopLast for Concat<L, R> {
+    type Last = R::Last;
+    type Init = Joined<L, R::Init>;
+}
+
+/// Concatenating two normalized IOPatterns into a normalized one: as both are normalized, only the last word of the
+/// first and the first word of the second may have to be merged.
+pub trait Fuse<R: List>: List {
+    /// The output of the concatenation
+    type Output: List;
+}
+
+/// Convenience trait for projection of Fuse
+pub type Fused<L, R> = <L as Fuse<R>>::Output;
+
+impl<R: List> Fuse<R> for Nil {
+    type Output = R;
+}
+
+/// The choice of how to concatenate the non-empty normalized patterns `L` and `R`, made on the pair of the last word
+/// of `L` and the first word of `R`, see `Fuse`.
+pub trait FuseAt<L: List, R: List> {
+    /// The output of the concatenation
+    type Output: List;
+}
+
+impl<N: Unsigned, M: Unsigned, L: List, R: List> FuseAt<L, R> for (Absorb<N>, Squeeze<M>) {
+    type Output = Concat<L, R>;
+}
+
+impl<N: Unsigned, M: Unsigned, L: List, R: List> FuseAt<L, R> for (Squeeze<N>, Absorb<M>) {
+    type Output = Concat<L, R>;
+}
+
+impl<N: Unsigned, M: Unsigned, L: PopLast, R: PopFirst> FuseAt<L, R> for (Absorb<N>, Absorb<M>)
+where
+    N: Add<M>, // present for all values in practice
+    Sum<N, M>: Unsigned,
+    L::Init: Join<Cons<Absorb<Sum<N, M>>, Nil>>,
+    Joined<L::Init, Cons<Absorb<Sum<N, M>>, Nil>>: Join<R::Rest>,
+{
+    type Output = Joined<Joined<L::Init, Cons<Absorb<Sum<N, M>>, Nil>>, R::Rest>;
+}
+
+impl<N: Unsigned, M: Unsigned, L: PopLast, R: PopFirst> FuseAt<L, R> for (Squeeze<N>, Squeeze<M>)
+where
+    N: Add<M>, // present for all values in practice
+    Sum<N, M>: Unsigned,
+    L::Init: Join<Cons<Squeeze<Sum<N, M>>, Nil>>,
+    Joined<L::Init, Cons<Squeeze<Sum<N, M>>, Nil>>: Join<R::Rest>,
+{
+    type Output = Joined<Joined<L::Init, Cons<Squeeze<Sum<N, M>>, Nil>>, R::Rest>;
+}
+
+// Join and Fuse, for the non-empty patterns: flat lists and concatenations
+macro_rules! impl_join_fuse {
+    ([$($l:ident: $bound:path),*] $lhs:ty) => {
+        impl<$($l: $bound),*> Join<Nil> for $lhs {
+            type Output = Self;
+        }
+
+        impl<$($l: $bound,)* H: IOWord, T: List> Join<Cons<H, T>> for $lhs {
+            type Output = Concat<Self, Cons<H, T>>;
+        }
+
+        impl<$($l: $bound,)* L2: List, R2: List> Join<Concat<L2, R2>> for $lhs {
+            type Output = Concat<Self, Concat<L2, R2>>;
+        }
+
+        impl<$($l: $bound),*> Fuse<Nil> for $lhs {
+            type Output = Self;
+        }
+
+        impl<$($l: $bound,)* H: IOWord, T: List> Fuse<Cons<H, T>> for $lhs
+        where
+            $lhs: PopLast,
+            (<$lhs as PopLast>::Last, H): FuseAt<Self, Cons<H, T>>,
+        {
+            type Output = <(<$lhs as PopLast>::Last, H) as FuseAt<Self, Cons<H, T>>>::Output;
+        }
+
+        impl<$($l: $bound,)* L2: List, R2: List> Fuse<Concat<L2, R2>> for $lhs
+        where
+            $lhs: PopLast,
+            Concat<L2, R2>: PopFirst,
+            (<$lhs as PopLast>::Last, <Concat<L2, R2> as PopFirst>::First): FuseAt<Self, Concat<L2, R2>>,
+        {
+            type Output = <(<$lhs as PopLast>::Last, <Concat<L2, R2> as PopFirst>::First) as FuseAt<
+                Self,
+                Concat<L2, R2>,
+            >>::Output;
+        }
+    };
+}
+
+impl_join_fuse!([H1: IOWord, T1: List] Cons<H1, T1>);
+impl_join_fuse!([L1: List, R1: List] Concat<L1, R1>);
+
 /// Emptying an IOPattern using an IOWord. This assumes that it is working
 /// with a list in head-normal form (i.e. the first element cannot be merged
 /// with the immediately following list). All lists that have been normalized
 /// are in head-normal form.
+/// The output is itself in head-normal form, see `HeadNormalize`: consuming from a normalized pattern
+/// only looks at its first words. The rest of the output is left as is, so that it may differ from the normal form
+/// of the same pattern, e.g. consuming `Squeeze<U3>` from `S3, A5, A1, S2, S1` outputs `A6, S2, S1`.
 pub trait Consume<Op: IOWord> {
     /// The output of the consumption
     type Output: List;
@@ -181,6 +554,14 @@ pub type Use<T, U> = <T as Consume<U>>::Output;
 
 // We unfold the type-level cases of the recurrence
 
+// A concatenation is consumed from its left side, which is not empty in a normalized pattern
+impl<Op: IOWord, L: List + Consume<Op>, R: List> Consume<Op> for Concat<L, R>
+where
+    Use<L, Op>: Join<R>,
+{
+    type Output = Joined<Use<L, Op>, R>;
+}
+
 // If the consumer is larger than the head pattern, we get to something
 // impossible, because we assume this is only called on normalized lists
 
@@ -207,9 +588,9 @@ where
     N: Unsigned,
     T: List,
     N: Sub<UInt<U, B>>, // present for N >= UInt<U, B>
-    Cons<Absorb<Diff<N, UInt<U, B>>>, T>: Normalize,
+    Cons<Absorb<Diff<N, UInt<U, B>>>, T>: HeadNormalize,
 {
-    type Output = Norm<Cons<Absorb<Diff<N, UInt<U, B>>>, T>>;
+    type Output = HeadNorm<Cons<Absorb<Diff<N, UInt<U, B>>>, T>>;
 }
 
 impl<U, B, N, T> Consume<Squeeze<UInt<U, B>>> for Cons<Squeeze<N>, T>
@@ -219,9 +600,9 @@ where
     N: Unsigned,
     T: List,
     N: Sub<UInt<U, B>>, // present for N >= UInt<U, B>
-    Cons<Squeeze<Diff<N, UInt<U, B>>>, T>: Normalize,
+    Cons<Squeeze<Diff<N, UInt<U, B>>>, T>: HeadNormalize,
 {
-    type Output = Norm<Cons<Squeeze<Diff<N, UInt<U, B>>>, T>>;
+    type Output = HeadNorm<Cons<Squeeze<Diff<N, UInt<U, B>>>, T>>;
 }
 
 // Seal the traits so that the above defines admissible implementations of sealed traits
@@ -233,11 +614,14 @@ mod private {
 
     impl Sealed for super::Nil {}
     impl<H, T: super::List> Sealed for super::Cons<H, T> {}
+    impl<L: super::List, R: super::List> Sealed for super::Concat<L, R> {}
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::format;
+    use std::prelude::rust_2021::*;
     use typenum::assert_type_eq;
     use typenum::{U1, U2, U3, U4, U5, U6};
 
@@ -271,6 +655,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn normalizes_concatenations() {
+        // both sides are normalized
+        assert_type_eq!(
+            Norm<Concat<iopat![Absorb<U2>, Absorb<U1>], iopat![Squeeze<U1>]>>,
+            Concat<iopat![Absorb<U3>], iopat![Squeeze<U1>]>
+        );
+        // the words at the boundary are merged
+        assert_type_eq!(
+            Norm<Concat<iopat![Squeeze<U1>, Absorb<U2>], iopat![Absorb<U3>, Squeeze<U1>]>>,
+            Concat<Concat<iopat![Squeeze<U1>], iopat![Absorb<U5>]>, iopat![Squeeze<U1>]>
+        );
+        assert_type_eq!(
+            Norm<Concat<iopat![Absorb<U1>], iopat![Absorb<U2>]>>,
+            iopat![Absorb<U3>]
+        );
+        // empty sides are elided
+        assert_type_eq!(
+            Norm<Concat<iopat![Absorb<U0>], iopat![Squeeze<U2>]>>,
+            iopat![Squeeze<U2>]
+        );
+        // a word followed by a concatenation
+        assert_type_eq!(
+            Norm<iopat![Absorb<U1>, ...Concat<iopat![Absorb<U1>], iopat![Squeeze<U1>]>]>,
+            Concat<iopat![Absorb<U2>], iopat![Squeeze<U1>]>
+        );
+    }
+
+    #[test]
+    fn displays() {
+        assert_eq!(PatternDisplay::<Nil>::new().to_string(), "");
+        assert_eq!(
+            PatternDisplay::<iopat![Absorb<U5>, Squeeze<U3>]>::new().to_string(),
+            "Absorb(5), Squeeze(3)"
+        );
+        assert_eq!(
+            format!("{:?}", PatternDisplay::<iopat![Squeeze<U1>]>::new()),
+            "[Squeeze(1)]"
+        );
+    }
+
     #[test]
     fn uses() {
         // Substraction
@@ -316,6 +741,16 @@ mod tests {
         );
         */
 
+        // Concatenations are consumed from the left
+        assert_type_eq!(
+            Use<Concat<iopat![Absorb<U2>], iopat![Squeeze<U1>]>, Absorb<U1>>,
+            Concat<iopat![Absorb<U1>], iopat![Squeeze<U1>]>
+        );
+        assert_type_eq!(
+            Use<Concat<iopat![Absorb<U2>], iopat![Squeeze<U1>]>, Absorb<U2>>,
+            iopat![Squeeze<U1>]
+        );
+
         // This, however, works
         assert_type_eq!(
             Use<Use<iopat![Squeeze<U3>, Absorb<U5>, Absorb<U1>], Squeeze<U3>>, Absorb<U6>>,
@@ -0,0 +1,147 @@
+//! This module contains the traits letting the ExtraSponge operate on several arrays at once,
+//! passed as a tuple (of arrays for absorbing, of mutable references to arrays for squeezing).
+//! The lengths of the arrays are summed at the type level, so that the whole tuple is checked against
+//! a single word of the IOPattern.
+
+use core::ops::Add;
+
+use hybrid_array::{Array, ArraySize};
+use typenum::{Sum, Unsigned, U0};
+
+/// A tuple of arrays of elements of type `T`, possibly of different lengths.
+pub trait ArrayTuple<T> {
+    /// The total length of the arrays in the tuple
+    type Len: Unsigned;
+
+    /// Calls `f` on the slices of the arrays of the tuple, in order.
+    fn with_slices<R>(&self, f: impl FnOnce(&[&[T]]) -> R) -> R;
+}
+
+impl<T> ArrayTuple<T> for () {
+    type Len = U0;
+
+    fn with_slices<R>(&self, f: impl FnOnce(&[&[T]]) -> R) -> R {
+        f(&[])
+    }
+}
+
+// The length of a tuple is defined as the sum of the length of its head and of the length of its tail,
+// which is itself a (smaller) tuple.
+macro_rules! impl_array_tuple {
+    ($u0:ident $i0:tt $(, $u:ident $i:tt)*) => {
+        impl<T, $u0 $(, $u)*> ArrayTuple<T> for (Array<T, $u0>, $(Array<T, $u>,)*)
+        where
+            $u0: ArraySize<T> + Add<<($(Array<T, $u>,)*) as ArrayTuple<T>>::Len>,
+            $($u: ArraySize<T>,)*
+            ($(Array<T, $u>,)*): ArrayTuple<T>,
+            Sum<$u0, <($(Array<T, $u>,)*) as ArrayTuple<T>>::Len>: Unsigned,
+        {
+            type Len = Sum<$u0, <($(Array<T, $u>,)*) as ArrayTuple<T>>::Len>;
+
+            fn with_slices<R>(&self, f: impl FnOnce(&[&[T]]) -> R) -> R {
+                f(&[self.$i0.as_slice() $(, self.$i.as_slice())*])
+            }
+        }
+    };
+}
+
+impl_array_tuple!(N1 0);
+impl_array_tuple!(N1 0, N2 1);
+impl_array_tuple!(N1 0, N2 1, N3 2);
+impl_array_tuple!(N1 0, N2 1, N3 2, N4 3);
+impl_array_tuple!(N1 0, N2 1, N3 2, N4 3, N5 4);
+impl_array_tuple!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5);
+impl_array_tuple!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5, N7 6);
+impl_array_tuple!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5, N7 6, N8 7);
+
+/// A tuple of mutable references to arrays of elements of type `T`, possibly of different lengths.
+pub trait ArrayTupleMut<T> {
+    /// The total length of the arrays in the tuple
+    type Len: Unsigned;
+
+    /// Calls `f` on the mutable slices of the arrays of the tuple, in order.
+    fn with_slices_mut<R>(self, f: impl FnOnce(&mut [&mut [T]]) -> R) -> R;
+}
+
+impl<T> ArrayTupleMut<T> for () {
+    type Len = U0;
+
+    fn with_slices_mut<R>(self, f: impl FnOnce(&mut [&mut [T]]) -> R) -> R {
+        f(&mut [])
+    }
+}
+
+macro_rules! impl_array_tuple_mut {
+    ($u0:ident $i0:tt $(, $u:ident $i:tt)*) => {
+        impl<'a, T, $u0 $(, $u)*> ArrayTupleMut<T> for (&'a mut Array<T, $u0>, $(&'a mut Array<T, $u>,)*)
+        where
+            $u0: ArraySize<T> + Add<<($(&'a mut Array<T, $u>,)*) as ArrayTupleMut<T>>::Len>,
+            $($u: ArraySize<T>,)*
+            ($(&'a mut Array<T, $u>,)*): ArrayTupleMut<T>,
+            Sum<$u0, <($(&'a mut Array<T, $u>,)*) as ArrayTupleMut<T>>::Len>: Unsigned,
+        {
+            type Len = Sum<$u0, <($(&'a mut Array<T, $u>,)*) as ArrayTupleMut<T>>::Len>;
+
+            fn with_slices_mut<R>(self, f: impl FnOnce(&mut [&mut [T]]) -> R) -> R {
+                f(&mut [self.$i0.as_mut_slice() $(, self.$i.as_mut_slice())*])
+            }
+        }
+    };
+}
+
+impl_array_tuple_mut!(N1 0);
+impl_array_tuple_mut!(N1 0, N2 1);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2, N4 3);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2, N4 3, N5 4);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5, N7 6);
+impl_array_tuple_mut!(N1 0, N2 1, N3 2, N4 3, N5 4, N6 5, N7 6, N8 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::prelude::rust_2021::*;
+    use std::vec;
+    use typenum::{assert_type_eq, U1, U2, U3, U5, U6};
+
+    #[allow(dead_code)]
+    type Len<T> = <T as ArrayTuple<u8>>::Len;
+
+    #[test]
+    fn tuple_lengths() {
+        assert_type_eq!(Len<()>, U0);
+        assert_type_eq!(Len<(Array<u8, U2>,)>, U2);
+        assert_type_eq!(Len<(Array<u8, U2>, Array<u8, U3>)>, U5);
+        assert_type_eq!(
+            Len<(Array<u8, U2>, Array<u8, U0>, Array<u8, U3>, Array<u8, U1>)>,
+            U6
+        );
+    }
+
+    #[test]
+    fn tuple_slices() {
+        let tuple = (
+            Array::<u8, U2>([1, 2]),
+            Array::<u8, U0>([]),
+            Array::<u8, U3>([3, 4, 5]),
+        );
+        let lens = tuple.with_slices(|parts| parts.iter().map(|p| p.len()).collect::<Vec<_>>());
+        assert_eq!(lens, vec![2, 0, 3]);
+    }
+
+    #[test]
+    fn tuple_slices_mut() {
+        let mut a = Array::<u8, U2>([0; 2]);
+        let mut b = Array::<u8, U3>([0; 3]);
+        (&mut a, &mut b).with_slices_mut(|parts| {
+            parts
+                .iter_mut()
+                .flat_map(|p| p.iter_mut())
+                .zip(1..)
+                .for_each(|(x, i)| *x = i)
+        });
+        assert_eq!(a.as_slice(), &[1, 2]);
+        assert_eq!(b.as_slice(), &[3, 4, 5]);
+    }
+}
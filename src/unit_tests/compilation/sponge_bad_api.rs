@@ -1,11 +1,11 @@
-use hybrid_array::{Array, ArrayOps};
+use hybrid_array::{Array, ArrayOps, ArraySize};
 use std::collections::VecDeque;
 use typenum::{U2, U3, U4};
 
 use extra_safe::{
     iopat,
     traits::{Absorb, Squeeze},
-    Error, ExtraSponge, IOPattern, SpongeAPI, SpongeOp,
+    squeeze_array_by_default, Error, ExtraSponge, IOPattern, SpongeAPI, SpongeOp,
 };
 
 // Copied from crate::unit_tests::sponge_instance
@@ -43,7 +43,7 @@ impl SpongeAPI for BasicSponge {
 
     fn start(&mut self, p: IOPattern, _: Option<u32>, acc: &mut Vec<u8>) {
         self.elements = acc.clone();
-        self.pattern = p.0.into_iter().collect();
+        self.pattern = p.iter().collect();
     }
 
     fn absorb(&mut self, length: u32, elements: &[u8], acc: &mut Vec<u8>) {
@@ -64,6 +64,10 @@ impl SpongeAPI for BasicSponge {
         }
     }
 
+    fn squeeze_array<U: ArraySize<u8>>(&mut self, acc: &mut Vec<u8>) -> Array<u8, U> {
+        squeeze_array_by_default(self, acc)
+    }
+
     fn finish(&mut self) -> Result<(), Error> {
         self.pattern
             .is_empty()
@@ -0,0 +1,8 @@
+use extra_safe::keccak::KeccakF1600;
+use extra_safe::sponge::SafeSponge;
+use typenum::{U16, U8};
+
+// The rate and capacity do not add up to the 25 lanes of Keccak-f[1600]
+fn hash(_sponge: SafeSponge<KeccakF1600, U16, U8>) {}
+
+fn main() {}
@@ -0,0 +1,643 @@
+// These tests check that long patterns, written either as trees of concatenations or as flat lists of words, which
+// `iopat!` splits into such trees, type-check under the default recursion limit of the compiler.
+
+use std::prelude::rust_2021::*;
+use std::vec;
+
+use hybrid_array::Array;
+use typenum::{U0, U1, U2, U3, U5};
+
+use crate::traits::{Absorb, Concat, Norm, Squeeze};
+use crate::{iopat, ExtraSponge, IOPattern, SpongeOp, ToIOPattern};
+
+use super::sponge_instance::BasicSponge;
+
+// 50 words, whose first and last words are merged when the chunk is repeated
+type Chunk = iopat![
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+];
+
+type P100 = Concat<Chunk, Chunk>;
+type P200 = Concat<P100, P100>;
+type P500 = Concat<Concat<P200, Chunk>, Concat<P200, Chunk>>;
+
+// Normalizing the pattern is checked at compile time, as part of the evaluation of this constant
+const NORMALIZED_LEN: usize = <Norm<P500> as ToIOPattern>::LEN;
+
+#[test]
+fn normalizes_500_words() {
+    assert_eq!(P500::LEN, 500);
+    assert_eq!(NORMALIZED_LEN, 261);
+    assert_eq!(
+        <Norm<P500>>::to_iopattern(),
+        P500::to_iopattern().normalize().unwrap()
+    );
+    assert_eq!(<Norm<P500>>::TOTAL_ABSORB, 630);
+    assert_eq!(<Norm<P500>>::TOTAL_SQUEEZE, 510);
+}
+
+// The words of `P500`, as a single flat list
+type Flat500 = iopat![
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+    Absorb<U2>,
+    Squeeze<U5>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Absorb<U5>,
+    Squeeze<U5>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U5>,
+    Absorb<U5>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U5>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U2>,
+    Absorb<U0>,
+    Absorb<U0>,
+    Squeeze<U0>,
+    Absorb<U3>,
+    Squeeze<U5>,
+    Absorb<U2>,
+    Squeeze<U2>,
+    Absorb<U3>,
+    Squeeze<U0>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U1>,
+    Squeeze<U1>,
+    Absorb<U1>,
+    Absorb<U1>,
+    Squeeze<U3>,
+    Absorb<U3>,
+    Absorb<U0>,
+    Squeeze<U5>,
+    Absorb<U0>,
+    Squeeze<U3>,
+    Absorb<U2>,
+];
+
+#[test]
+fn normalizes_500_flat_words() {
+    assert_eq!(Flat500::LEN, 500);
+    assert_eq!(<Norm<Flat500> as ToIOPattern>::LEN, NORMALIZED_LEN);
+    assert_eq!(Flat500::to_iopattern(), P500::to_iopattern());
+    assert_eq!(
+        <Norm<Flat500>>::to_iopattern(),
+        <Norm<P500>>::to_iopattern()
+    );
+}
+
+#[test]
+fn concatenations_iterate_in_order() {
+    type P = Concat<
+        iopat![Absorb<U1>, Squeeze<U2>],
+        Concat<Concat<iopat![], iopat![Absorb<U3>]>, iopat![Squeeze<U5>]>,
+    >;
+    const EXPECTED: &[SpongeOp] = &[
+        SpongeOp::Absorb(1),
+        SpongeOp::Squeeze(2),
+        SpongeOp::Absorb(3),
+        SpongeOp::Squeeze(5),
+    ];
+    assert_eq!(P::to_iopattern(), IOPattern::from_static(EXPECTED));
+    assert_eq!(P::to_iopattern().iter().len(), 4);
+    assert_eq!(P::NEXT_OP, Some(SpongeOp::Absorb(1)));
+}
+
+#[test]
+fn sponge_over_concatenation() {
+    type Half = iopat![Absorb<U2>, Squeeze<U1>, Absorb<U3>];
+    let mut acc = vec![1, 2, 3];
+
+    // the boundary words are merged into Absorb(5)
+    let sponge = ExtraSponge::<BasicSponge, Concat<Half, Half>>::start(
+        None,
+        BasicSponge::default(),
+        &mut acc,
+    );
+    assert_eq!(
+        sponge.remaining(),
+        "A2, S1, A5, S1, A3".parse::<IOPattern>().unwrap()
+    );
+    let sponge = sponge.absorb(Array::<u8, U2>([1, 2]), &mut acc);
+    let sponge = sponge.squeeze(&mut Array::<u8, U1>([0]), &mut acc);
+    let sponge = sponge.absorb(Array::<u8, U3>([3, 4, 5]), &mut acc);
+    let sponge = sponge.absorb(Array::<u8, U2>([6, 7]), &mut acc);
+    let sponge = sponge.squeeze(&mut Array::<u8, U1>([0]), &mut acc);
+    assert_eq!(sponge.next_op(), Some(SpongeOp::Absorb(3)));
+    let sponge = sponge.absorb(Array::<u8, U3>([8, 9, 10]), &mut acc);
+    assert!(sponge.is_complete());
+}
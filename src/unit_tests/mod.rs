@@ -1,14 +1,38 @@
-use hybrid_array::{Array, ArrayOps};
-use typenum::{U2, U3, U5};
+#![allow(clippy::type_complexity)]
+
+use core::num::NonZeroU8;
+use std::prelude::rust_2021::*;
+use std::{format, vec};
+
+use hybrid_array::{Array, ArrayOps, ArraySize};
+use typenum::{U0, U1, U1000, U1048576, U16, U17, U2, U3, U31, U32, U40, U5, U524288, U6, U8, U9};
 
 use crate::{
+    array_from_fn,
+    codec::{BytePacking, CanonicalValue, Packed},
+    field::{Bls12_381Scalar, Field, Goldilocks},
     iopat,
+    keccak::KeccakF1600,
+    owned::AccSponge,
+    poseidon::{Poseidon, PoseidonSponge},
+    poseidon2::{Poseidon2, Poseidon2Sponge},
+    sponge::{KeccakByteSponge, SafeSponge},
+    squeeze_array_by_default,
     traits::{Absorb, Nil, Squeeze},
-    ExtraSponge,
+    Error, ExtraSponge, IOPattern, NativeSpongeAPI, SpongeOp,
 };
 
+mod long_patterns;
+// The dummy sponge predates the lints of the test targets
+#[allow(
+    clippy::assign_op_pattern,
+    clippy::derivable_impls,
+    clippy::let_unit_value,
+    clippy::manual_memcpy,
+    clippy::unnecessary_lazy_evaluations
+)]
 mod sponge_instance;
-use sponge_instance::BasicSponge;
+use sponge_instance::{BasicSponge, NativeBasicSponge};
 
 // This pattern-matches the type checker output, so works only on stable
 #[rustversion::stable]
@@ -18,6 +42,28 @@ fn illegal_api_uses() {
     t.compile_fail("src/unit_tests/compilation/*.rs");
 }
 
+// This checks the library builds against `core` only, with and without `alloc`.
+// As trybuild does, it uses its own target directory, to not contend for the lock of the one running the tests.
+#[test]
+fn builds_without_std() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    for features in [&[][..], &["--features", "alloc"]] {
+        let output = std::process::Command::new(env!("CARGO"))
+            .args(["check", "--lib", "--quiet", "--no-default-features"])
+            .args(features)
+            .arg("--target-dir")
+            .arg(format!("{manifest_dir}/target/no_std"))
+            .current_dir(manifest_dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "building with {features:?} failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
+
 // THis works because we empty the sponge before dropping it
 #[test]
 fn test_extrasponge_instance() {
@@ -41,7 +87,7 @@ fn test_extrasponge_instance() {
 }
 
 // This panics, because we drop it without emptying it
-#[should_panic]
+#[should_panic(expected = "remaining: Absorb(5), Squeeze(3)")]
 #[test]
 fn test_extrasponge_instance_drop() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
@@ -54,3 +100,601 @@ fn test_extrasponge_instance_drop() {
             &mut start_acc,
         );
 }
+
+// A sponge over u32 which records its pattern and the elements it absorbs, and squeezes them back.
+// It is borrowed by the ExtraSponge, so that the records outlive the latter.
+#[derive(Debug, Default)]
+struct PackingRecorder {
+    pattern: Option<IOPattern>,
+    absorbed: Vec<u32>,
+}
+
+impl NativeSpongeAPI for &mut PackingRecorder {
+    type Value = u32;
+
+    fn start(&mut self, p: IOPattern, _: Option<u32>) {
+        self.pattern = Some(p);
+    }
+
+    fn absorb(&mut self, _: u32, elements: &[u32]) {
+        self.absorbed.extend_from_slice(elements);
+    }
+
+    fn squeeze(&mut self, _: u32, elements: &mut [u32]) {
+        elements.copy_from_slice(&self.absorbed[..elements.len()]);
+    }
+
+    fn squeeze_array<U: ArraySize<u32>>(&mut self) -> Array<u32, U> {
+        squeeze_array_by_default(self, &mut ())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_extrasponge_bytes() {
+    // 9 bytes take 3 elements of 4 bytes, the last of which holds a single byte
+    typenum::assert_type_eq!(Packed<U9, u32>, U3);
+    typenum::assert_type_eq!(Packed<U6, u32>, U2);
+
+    let mut sponge = PackingRecorder::default();
+    let extra_sponge = ExtraSponge::<
+        &mut PackingRecorder,
+        iopat![Absorb<Packed<U9, u32>>, Squeeze<Packed<U6, u32>>],
+    >::start(None, &mut sponge, &mut ());
+    let extra_sponge = extra_sponge.absorb_bytes(
+        Array::<u8, U9>::from_core_array([1, 2, 3, 4, 5, 6, 7, 8, 9]),
+        &mut (),
+    );
+    let mut out = Array::<u8, U6>::from_core_array([0u8; 6]);
+    let extra_sponge: ExtraSponge<_, Nil> = extra_sponge.squeeze_bytes(&mut out, &mut ());
+    extra_sponge.finish().unwrap();
+
+    assert_eq!(sponge.pattern, Some("A3, S2".parse().unwrap()));
+    assert_eq!(sponge.absorbed, [0x0403_0201, 0x0807_0605, 0x0000_0009]);
+    // the second element squeezed is truncated to its first two bytes
+    assert_eq!(out.as_slice(), &[1, 2, 3, 4, 5, 6]);
+}
+
+// Bytes are packed 31 to an element of the scalar field of BLS12-381
+#[test]
+fn test_field_sponge_bytes() {
+    type Sponge = PoseidonSponge<Bls12_381Scalar, U2, U1>;
+    let poseidon = Poseidon::new(8, 57, 5);
+    let bytes: [u8; 40] = core::array::from_fn(|i| i as u8);
+
+    let extra_sponge = ExtraSponge::<Sponge, iopat![Absorb<U2>, Squeeze<U1>]>::start(
+        None,
+        Sponge::new(poseidon.clone()),
+        &mut (),
+    );
+    let mut out = Array::<u8, U31>::from_core_array([0u8; 31]);
+    let _extra_sponge_2: ExtraSponge<Sponge, Nil> = extra_sponge
+        .absorb_bytes(Array::<u8, U40>::from_core_array(bytes), &mut ())
+        .squeeze_bytes(&mut out, &mut ());
+
+    let mut sponge = Sponge::new(poseidon);
+    let mut expected = [Bls12_381Scalar::ZERO];
+    NativeSpongeAPI::start(&mut sponge, "A2, S1".parse().unwrap(), None);
+    NativeSpongeAPI::absorb(
+        &mut sponge,
+        2,
+        &[
+            Bls12_381Scalar::pack(&bytes[..31]),
+            Bls12_381Scalar::pack(&bytes[31..]),
+        ],
+    );
+    NativeSpongeAPI::squeeze(&mut sponge, 1, &mut expected);
+    NativeSpongeAPI::finish(&mut sponge).unwrap();
+    assert_eq!(out.as_slice(), &expected[0].to_bytes()[..31]);
+}
+
+#[test]
+fn test_extrasponge_squeeze_array() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    let extra_sponge_2 = extra_sponge.absorb(
+        Array::from_core_array([4u8, 5, 6, 7, 8]),
+        &mut Vec::default(),
+    );
+    let (out, _extra_sponge_3): (Array<u8, U3>, ExtraSponge<BasicSponge, Nil>) =
+        extra_sponge_2.squeeze_array(&mut Vec::default());
+    assert_eq!(out.as_slice(), &[1, 2, 3]);
+}
+
+#[test]
+fn test_extrasponge_squeeze_array_without_default() {
+    // A sponge squeezing successive non-zero bytes, a type with no default
+    #[derive(Debug, Default)]
+    struct Counter(u8);
+
+    impl NativeSpongeAPI for Counter {
+        type Value = NonZeroU8;
+
+        fn start(&mut self, _: IOPattern, _: Option<u32>) {}
+
+        fn absorb(&mut self, _: u32, _: &[NonZeroU8]) {}
+
+        fn squeeze(&mut self, _: u32, elements: &mut [NonZeroU8]) {
+            for element in elements {
+                self.0 += 1;
+                *element = NonZeroU8::new(self.0).unwrap();
+            }
+        }
+
+        fn squeeze_array<U: ArraySize<NonZeroU8>>(&mut self) -> Array<NonZeroU8, U> {
+            array_from_fn(|_| {
+                self.0 += 1;
+                NonZeroU8::new(self.0).unwrap()
+            })
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    let extra_sponge =
+        ExtraSponge::<Counter, iopat![Squeeze<U3>]>::start(None, Counter::default(), &mut ());
+    let (out, _extra_sponge_2): (Array<NonZeroU8, U3>, ExtraSponge<Counter, Nil>) =
+        extra_sponge.squeeze_array(&mut ());
+    assert_eq!(out.map(NonZeroU8::get), [1, 2, 3]);
+}
+
+#[test]
+fn test_accsponge_instance() {
+    let acc_sponge = AccSponge::<BasicSponge, iopat![Absorb<U2>, Absorb<U3>, Squeeze<U3>]>::start(
+        None,
+        BasicSponge::default(),
+        vec![1, 2, 3],
+    );
+
+    let acc_sponge_2 = acc_sponge.absorb(Array::from_core_array([0u8; 5]));
+    let (out, acc_sponge_3): (Array<u8, U3>, AccSponge<BasicSponge, Nil>) =
+        acc_sponge_2.squeeze_array();
+    assert_eq!(out.as_slice(), &[1, 2, 3]);
+    assert_eq!(acc_sponge_3.finish().unwrap(), vec![1, 2, 3]);
+}
+
+// The error of the underlying sponge is returned, rather than raised when dropping it
+#[test]
+fn test_accsponge_finish_error() {
+    #[derive(Debug, Default)]
+    struct FailingSponge(NativeBasicSponge);
+
+    impl NativeSpongeAPI for FailingSponge {
+        type Value = u8;
+
+        fn start(&mut self, p: IOPattern, domain_separator: Option<u32>) {
+            self.0.start(p, domain_separator);
+        }
+
+        fn absorb(&mut self, length: u32, elements: &[u8]) {
+            self.0.absorb(length, elements);
+        }
+
+        fn squeeze(&mut self, length: u32, elements: &mut [u8]) {
+            self.0.squeeze(length, elements);
+        }
+
+        fn squeeze_array<U: ArraySize<u8>>(&mut self) -> Array<u8, U> {
+            squeeze_array_by_default(self, &mut ())
+        }
+
+        fn finish(&mut self) -> Result<(), Error> {
+            Err(Error::ParameterUsageMismatch)
+        }
+    }
+
+    let acc_sponge =
+        AccSponge::<FailingSponge, iopat![Absorb<U2>]>::start(None, FailingSponge::default(), ());
+    let res = acc_sponge.absorb(Array::from_core_array([0u8; 2])).finish();
+    assert!(matches!(res, Err(Error::ParameterUsageMismatch)));
+}
+
+#[test]
+fn test_native_sponge_instance() {
+    let acc_sponge = AccSponge::<NativeBasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        NativeBasicSponge::default(),
+        (),
+    );
+
+    let acc_sponge_2 = acc_sponge.absorb(Array::from_core_array([0u8; 5]));
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    acc_sponge_2.squeeze(&mut out).finish().unwrap();
+    assert_eq!(out.as_slice(), &[1, 2, 3]);
+}
+
+#[should_panic]
+#[test]
+fn test_accsponge_instance_drop() {
+    let acc_sponge = AccSponge::<NativeBasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        NativeBasicSponge::default(),
+        (),
+    );
+    let _acc_sponge_2 = acc_sponge.absorb(Array::from_core_array([0u8; 5]));
+}
+
+#[test]
+fn test_extrasponge_absorb_slice() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    let data = vec![0u8; 6];
+    // the wrong length hands us the sponge back
+    let (extra_sponge, err) = extra_sponge
+        .absorb_slice::<U5>(&data, &mut Vec::default())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LengthMismatch {
+            expected: 5,
+            actual: 6
+        }
+    ));
+    let extra_sponge_2 = extra_sponge
+        .absorb_slice::<U2>(&data[..2], &mut Vec::default())
+        .unwrap();
+    let extra_sponge_3 =
+        extra_sponge_2.absorb_ref(&Array::from_core_array([0u8; 3]), &mut Vec::default());
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let _extra_sponge_4: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_3.squeeze(&mut out, &mut Vec::default());
+}
+
+#[test]
+fn test_extrasponge_absorb_iter() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U1000>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    // an iterator of known, wrong size is rejected before anything is absorbed
+    let (extra_sponge, err) = extra_sponge
+        .absorb_iter::<U1000, _>(0..=255u8, &mut Vec::default())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LengthMismatch {
+            expected: 1000,
+            actual: 256
+        }
+    ));
+    // fed in several chunks
+    let elements = (0..1000).map(|i| (i % 256) as u8);
+    let extra_sponge_2 = extra_sponge
+        .absorb_iter::<U1000, _>(elements, &mut Vec::default())
+        .unwrap();
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_2.squeeze(&mut out, &mut Vec::default());
+}
+
+#[test]
+fn test_extrasponge_absorb_iter_unknown_size() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    // filtering loses the size information, the extra element is found once the elements are buffered
+    let elements = (0..6u8).filter(|_| true);
+    let (extra_sponge, err) = extra_sponge
+        .absorb_iter::<U5, _>(elements, &mut Vec::default())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LengthMismatch {
+            expected: 5,
+            actual: 6
+        }
+    ));
+    // nothing was absorbed
+    let extra_sponge_2 = extra_sponge
+        .absorb_iter::<U5, _>((0..5u8).filter(|_| true), &mut Vec::default())
+        .unwrap();
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_2.squeeze(&mut out, &mut Vec::default());
+}
+
+#[test]
+fn test_extrasponge_absorb_iter_missing_elements() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U1000>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    // the missing element spans several chunks, and is found before anything is absorbed
+    let elements = (0..).take_while(|&i| i < 999).map(|i| (i % 256) as u8);
+    let (extra_sponge, err) = extra_sponge
+        .absorb_iter::<U1000, _>(elements, &mut Vec::default())
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LengthMismatch {
+            expected: 1000,
+            actual: 999
+        }
+    ));
+    let elements = (0..).take_while(|&i| i < 1000).map(|i| (i % 256) as u8);
+    let extra_sponge_2 = extra_sponge
+        .absorb_iter::<U1000, _>(elements, &mut Vec::default())
+        .unwrap();
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_2.squeeze(&mut out, &mut Vec::default());
+}
+
+#[test]
+#[should_panic(expected = "does not yield as many elements as its size_hint gives")]
+fn test_extrasponge_absorb_iter_wrong_size_hint() {
+    // An iterator claiming one more element than it yields
+    struct Liar(core::ops::Range<u8>);
+
+    impl Iterator for Liar {
+        type Item = u8;
+
+        fn next(&mut self) -> Option<u8> {
+            self.0.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let len = self.0.len() + 1;
+            (len, Some(len))
+        }
+    }
+
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>]>::start(
+        None,
+        BasicSponge::default(),
+        &mut start_acc,
+    );
+    let _ = extra_sponge.absorb_iter::<U5, _>(Liar(0..4), &mut Vec::default());
+}
+
+#[test]
+fn test_extrasponge_large_slices() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    // these sizes are not supported by hybrid_array
+    let extra_sponge =
+        ExtraSponge::<BasicSponge, iopat![Absorb<U1048576>, Squeeze<U524288>]>::start(
+            None,
+            basic_sponge,
+            &mut start_acc,
+        );
+
+    let data = vec![0u8; 1 << 20];
+    let (first_half, second_half) = data.split_at(1 << 19);
+    let extra_sponge_2 = extra_sponge
+        .absorb_slice::<U524288>(first_half, &mut Vec::default())
+        .unwrap()
+        .absorb_slice::<U524288>(second_half, &mut Vec::default())
+        .unwrap();
+    let mut out = vec![0u8; 1 << 19];
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> = extra_sponge_2
+        .squeeze_slice::<U524288>(&mut out, &mut Vec::default())
+        .unwrap();
+}
+
+#[test]
+fn test_extrasponge_absorb_many() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>, Squeeze<U3>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    let x = Array::<u8, U2>::from_core_array([1, 2]);
+    let y = Array::<u8, U3>::from_core_array([3, 4, 5]);
+    let extra_sponge_2 = extra_sponge.absorb_many((x, Array::<u8, U0>([]), y), &mut Vec::default());
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_2.squeeze(&mut out, &mut Vec::default());
+}
+
+#[test]
+fn test_extrasponge_squeeze_into() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge = ExtraSponge::<BasicSponge, iopat![Absorb<U5>, Squeeze<U5>]>::start(
+        None,
+        basic_sponge,
+        &mut start_acc,
+    );
+
+    let extra_sponge_2 = extra_sponge.absorb(
+        Array::from_core_array([4u8, 5, 6, 7, 8]),
+        &mut Vec::default(),
+    );
+    let mut x = Array::<u8, U2>::from_core_array([0; 2]);
+    let mut y = Array::<u8, U3>::from_core_array([0; 3]);
+    let _extra_sponge_3: ExtraSponge<BasicSponge, Nil> =
+        extra_sponge_2.squeeze_into((&mut x, &mut y), &mut Vec::default());
+    // our test sponge squeezes from the start of its state every time
+    assert_eq!(x.as_slice(), &[1, 2]);
+    assert_eq!(y.as_slice(), &[1, 2, 3]);
+}
+
+#[test]
+fn test_extrasponge_introspection() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge =
+        ExtraSponge::<BasicSponge, iopat![Absorb<U2>, Absorb<U3>, Squeeze<U3>]>::start(
+            None,
+            basic_sponge,
+            &mut start_acc,
+        );
+    assert_eq!(
+        extra_sponge.remaining(),
+        IOPattern::new(vec![SpongeOp::Absorb(5), SpongeOp::Squeeze(3)])
+    );
+    assert_eq!(extra_sponge.next_op(), Some(SpongeOp::Absorb(5)));
+    assert_eq!(extra_sponge.remaining_absorb(), 5);
+    assert_eq!(extra_sponge.remaining_squeeze(), 3);
+    assert!(!extra_sponge.is_complete());
+
+    let extra_sponge_2 = extra_sponge.absorb(Array::from_core_array([0u8; 2]), &mut Vec::default());
+    assert_eq!(extra_sponge_2.next_op(), Some(SpongeOp::Absorb(3)));
+    assert_eq!(extra_sponge_2.remaining_absorb(), 3);
+
+    let extra_sponge_3 =
+        extra_sponge_2.absorb(Array::from_core_array([0u8; 3]), &mut Vec::default());
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let extra_sponge_4 = extra_sponge_3.squeeze(&mut out, &mut Vec::default());
+    assert_eq!(extra_sponge_4.next_op(), None);
+    assert_eq!(extra_sponge_4.remaining(), IOPattern::new(vec![]));
+    assert!(extra_sponge_4.is_complete());
+}
+
+#[test]
+fn test_extrasponge_debug() {
+    let mut start_acc: Vec<u8> = vec![1, 2, 3];
+    let basic_sponge = BasicSponge::default();
+
+    let extra_sponge =
+        ExtraSponge::<BasicSponge, iopat![Absorb<U2>, Absorb<U3>, Squeeze<U3>]>::start(
+            None,
+            basic_sponge,
+            &mut start_acc,
+        );
+    assert!(format!("{extra_sponge:?}").ends_with("pattern: [Absorb(5), Squeeze(3)] }"));
+    assert_eq!(
+        extra_sponge.remaining().to_string(),
+        "Absorb(5), Squeeze(3)"
+    );
+
+    let extra_sponge_2 = extra_sponge.absorb(Array::from_core_array([0u8; 5]), &mut Vec::default());
+    let mut out = Array::<u8, U3>::from_core_array([0u8; 3]);
+    let extra_sponge_3 = extra_sponge_2.squeeze(&mut out, &mut Vec::default());
+    assert!(format!("{extra_sponge_3:?}").ends_with("pattern: [] }"));
+}
+
+// The reference sponge follows the pattern the ExtraSponge checks at compile time, and squeezes
+// the same elements as when it is called directly
+#[test]
+fn test_safe_sponge_instance() {
+    type KeccakSponge = SafeSponge<KeccakF1600, U17, U8>;
+
+    let extra_sponge =
+        ExtraSponge::<KeccakSponge, iopat![Absorb<U2>, Absorb<U3>, Squeeze<U3>]>::start(
+            Some(7),
+            KeccakSponge::default(),
+            &mut (),
+        );
+    let extra_sponge_2 = extra_sponge
+        .absorb(Array::from_core_array([1u64, 2]), &mut ())
+        .absorb(Array::from_core_array([3u64, 4, 5]), &mut ());
+    let (out, _extra_sponge_3): (Array<u64, U3>, ExtraSponge<KeccakSponge, Nil>) =
+        extra_sponge_2.squeeze_array(&mut ());
+
+    let mut sponge = KeccakSponge::default();
+    let mut expected = [0u64; 3];
+    NativeSpongeAPI::start(&mut sponge, "A5, S3".parse().unwrap(), Some(7));
+    NativeSpongeAPI::absorb(&mut sponge, 5, &[1, 2, 3, 4, 5]);
+    NativeSpongeAPI::squeeze(&mut sponge, 3, &mut expected);
+    NativeSpongeAPI::finish(&mut sponge).unwrap();
+    assert_eq!(out.as_slice(), &expected);
+}
+
+#[test]
+fn test_poseidon2_sponge_instance() {
+    // the absorption and the squeezing span several permutations of the rate of 2 elements
+    type Sponge = Poseidon2Sponge<Bls12_381Scalar, U2, U1>;
+    let poseidon2 = Poseidon2::new(8, 56, 5);
+    let input: [Bls12_381Scalar; 6] = core::array::from_fn(|i| Bls12_381Scalar::from_u64(i as u64));
+
+    let extra_sponge = ExtraSponge::<
+        Sponge,
+        iopat![Absorb<U2>, Absorb<U3>, Squeeze<U5>, Absorb<U1>, Squeeze<U2>],
+    >::start(Some(3), Sponge::new(poseidon2.clone()), &mut ());
+    let (out, extra_sponge_2) = extra_sponge
+        .absorb(Array::from_core_array([input[0], input[1]]), &mut ())
+        .absorb(
+            Array::from_core_array([input[2], input[3], input[4]]),
+            &mut (),
+        )
+        .squeeze_array::<U5>(&mut ());
+    let (out_2, _extra_sponge_3): (Array<Bls12_381Scalar, U2>, ExtraSponge<Sponge, Nil>) =
+        extra_sponge_2
+            .absorb(Array::from_core_array([input[5]]), &mut ())
+            .squeeze_array(&mut ());
+
+    let mut sponge = Sponge::new(poseidon2);
+    let mut expected = [Bls12_381Scalar::ZERO; 5];
+    let mut expected_2 = [Bls12_381Scalar::ZERO; 2];
+    NativeSpongeAPI::start(&mut sponge, "A5, S5, A1, S2".parse().unwrap(), Some(3));
+    NativeSpongeAPI::absorb(&mut sponge, 5, &input[..5]);
+    NativeSpongeAPI::squeeze(&mut sponge, 5, &mut expected);
+    NativeSpongeAPI::absorb(&mut sponge, 1, &input[5..]);
+    NativeSpongeAPI::squeeze(&mut sponge, 2, &mut expected_2);
+    NativeSpongeAPI::finish(&mut sponge).unwrap();
+    assert_eq!(out.as_slice(), &expected);
+    assert_eq!(out_2.as_slice(), &expected_2);
+    assert_ne!(out[0], out_2[0]);
+}
+
+#[test]
+fn test_keccak_byte_sponge_instance() {
+    // a native transcript, absorbing the canonical encodings of field elements
+    let a = Bls12_381Scalar::from_u64(0x5afe);
+    let b = Goldilocks::from_u64(0xcafe);
+
+    let extra_sponge =
+        ExtraSponge::<KeccakByteSponge, iopat![Absorb<U32>, Absorb<U8>, Squeeze<U16>]>::start(
+            Some(1),
+            KeccakByteSponge::new(),
+            &mut (),
+        );
+    let (out, _extra_sponge_2): (Array<u8, U16>, ExtraSponge<KeccakByteSponge, Nil>) = extra_sponge
+        .absorb(a.to_bytes(), &mut ())
+        .absorb(b.to_bytes(), &mut ())
+        .squeeze_array(&mut ());
+
+    let mut sponge = KeccakByteSponge::new();
+    let mut expected = [0u8; 16];
+    NativeSpongeAPI::start(&mut sponge, "A40, S16".parse().unwrap(), Some(1));
+    NativeSpongeAPI::absorb(&mut sponge, 32, &a.to_bytes().0);
+    NativeSpongeAPI::absorb(&mut sponge, 8, &b.to_bytes().0);
+    NativeSpongeAPI::squeeze(&mut sponge, 16, &mut expected);
+    NativeSpongeAPI::finish(&mut sponge).unwrap();
+    assert_eq!(out.as_slice(), &expected);
+}
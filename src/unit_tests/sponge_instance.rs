@@ -1,14 +1,34 @@
 use std::collections::VecDeque;
+use std::prelude::rust_2021::*;
+use std::vec;
 
-use crate::{Error, IOPattern, SpongeAPI, SpongeOp};
+use hybrid_array::{Array, ArraySize};
+
+use crate::{squeeze_array_by_default, Error, IOPattern, NativeSpongeAPI, SpongeAPI, SpongeOp};
 
 #[allow(unreachable_pub)]
+#[derive(Debug)]
 pub struct BasicSponge {
     elements: Vec<u8>,
     pattern: VecDeque<SpongeOp>,
 }
 
 impl BasicSponge {
+    // Consumes the operation from the head of the pattern, allowing successive calls
+    // to add up to a single word of the pattern.
+    fn use_pattern(&mut self, op: SpongeOp) {
+        let word = self.pattern.pop_front().unwrap();
+        match (word, op) {
+            (SpongeOp::Absorb(n), SpongeOp::Absorb(k)) if n > k => {
+                self.pattern.push_front(SpongeOp::Absorb(n - k))
+            }
+            (SpongeOp::Squeeze(n), SpongeOp::Squeeze(k)) if n > k => {
+                self.pattern.push_front(SpongeOp::Squeeze(n - k))
+            }
+            _ => assert_eq!(word, op),
+        }
+    }
+
     fn permute(&mut self, other_elems: &Vec<u8>) {
         self.elements
             .iter_mut()
@@ -36,27 +56,29 @@ impl SpongeAPI for BasicSponge {
 
     fn start(&mut self, p: IOPattern, _: Option<u32>, acc: &mut Vec<u8>) {
         self.elements = acc.clone();
-        self.pattern = p.0.into_iter().collect();
+        self.pattern = p.iter().collect();
     }
 
     fn absorb(&mut self, length: u32, elements: &[u8], acc: &mut Vec<u8>) {
         assert_eq!(length as usize, elements.len());
-        let word = self.pattern.pop_front().unwrap();
-        assert_eq!(word, SpongeOp::Absorb(length));
+        self.use_pattern(SpongeOp::Absorb(length));
         self.permute(acc);
         self.elements.extend_from_slice(elements);
     }
 
     fn squeeze(&mut self, length: u32, elements: &mut [u8], acc: &mut Vec<u8>) {
         assert_eq!(length as usize, elements.len());
-        let word = self.pattern.pop_front().unwrap();
-        assert_eq!(word, SpongeOp::Squeeze(length));
+        self.use_pattern(SpongeOp::Squeeze(length));
         self.permute(acc);
         for i in 0..length as usize {
             elements[i] = self.elements[i];
         }
     }
 
+    fn squeeze_array<U: ArraySize<u8>>(&mut self, acc: &mut Vec<u8>) -> Array<u8, U> {
+        squeeze_array_by_default(self, acc)
+    }
+
     fn finish(&mut self) -> Result<(), Error> {
         self.pattern
             .is_empty()
@@ -65,12 +87,41 @@ impl SpongeAPI for BasicSponge {
     }
 }
 
+// A version of the above without accumulator, which always starts with the same elements.
+#[allow(unreachable_pub)]
+#[derive(Debug, Default)]
+pub struct NativeBasicSponge(BasicSponge);
+
+impl NativeSpongeAPI for NativeBasicSponge {
+    type Value = u8;
+
+    fn start(&mut self, p: IOPattern, domain_separator: Option<u32>) {
+        self.0.start(p, domain_separator, &mut vec![1, 2, 3]);
+    }
+
+    fn absorb(&mut self, length: u32, elements: &[u8]) {
+        self.0.absorb(length, elements, &mut Vec::default());
+    }
+
+    fn squeeze(&mut self, length: u32, elements: &mut [u8]) {
+        self.0.squeeze(length, elements, &mut Vec::default());
+    }
+
+    fn squeeze_array<U: ArraySize<u8>>(&mut self) -> Array<u8, U> {
+        squeeze_array_by_default(self, &mut ())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.0.finish()
+    }
+}
+
 // This just tests our dummy type BasicSponge, to make sure it works as expected.
 #[test]
 fn test_start() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
     let _basic_sponge = BasicSponge::default().start(
-        IOPattern(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
+        IOPattern::new(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
         None,
         &mut start_acc,
     );
@@ -81,7 +132,7 @@ fn test_pattern() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
     let mut basic_sponge = BasicSponge::default();
     basic_sponge.start(
-        IOPattern(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
+        IOPattern::new(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
         None,
         &mut start_acc,
     );
@@ -96,7 +147,7 @@ fn test_base_api_doesnt_protect_forgotten_finish() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
     let mut basic_sponge = BasicSponge::default();
     basic_sponge.start(
-        IOPattern(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
+        IOPattern::new(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
         None,
         &mut start_acc,
     );
@@ -111,7 +162,7 @@ fn test_bad_pattern() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
     let mut basic_sponge = BasicSponge::default();
     basic_sponge.start(
-        IOPattern(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
+        IOPattern::new(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
         None,
         &mut start_acc,
     );
@@ -127,7 +178,7 @@ fn test_bad_finish() {
     let mut start_acc: Vec<u8> = vec![1, 2, 3];
     let mut basic_sponge = BasicSponge::default();
     basic_sponge.start(
-        IOPattern(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
+        IOPattern::new(vec![SpongeOp::Absorb(1), SpongeOp::Squeeze(1)]),
         None,
         &mut start_acc,
     );
@@ -0,0 +1,165 @@
+//! Differential tests between the type-level pattern operations (`Norm`, `Use`, and `Norm` of a `Concat`) and their
+//! term-level counterparts (`IOPattern::normalize`, `IOPattern::consume`, and `IOPattern::concat`).
+//! The patterns are drawn from a fixed-seed pseudo-random generator, so the corpus is deterministic. They are long
+//! enough for `iopat!` to split them, and those given to `Use` and `Concat` are written as random trees of
+//! concatenations. As the type-level operations are checked by the compiler, the corpus is written as a program,
+//! which trybuild compiles against the crate and runs.
+
+use std::fmt::Write;
+use std::fs;
+
+// The number of patterns tested against each operation
+const CORPUS_SIZE: usize = 256;
+// above the 16 words which `iopat!` splits into a `Concat`
+const MAX_PATTERN_LEN: u64 = 40;
+const MAX_COUNT: u64 = 12;
+
+// xorshift64*, which is plenty for drawing test cases
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Absorb(u64),
+    Squeeze(u64),
+}
+
+impl Op {
+    fn to_type(self) -> String {
+        match self {
+            Op::Absorb(n) => format!("Absorb<typenum::U{n}>"),
+            Op::Squeeze(n) => format!("Squeeze<typenum::U{n}>"),
+        }
+    }
+}
+
+fn random_pattern(rng: &mut Rng) -> Vec<Op> {
+    let len = 1 + rng.below(MAX_PATTERN_LEN);
+    (0..len)
+        .map(|_| {
+            // zero counts are over-represented, as they exercise the elision rules
+            let count = if rng.below(4) == 0 {
+                0
+            } else {
+                rng.below(MAX_COUNT + 1)
+            };
+            if rng.below(2) == 0 {
+                Op::Absorb(count)
+            } else {
+                Op::Squeeze(count)
+            }
+        })
+        .collect()
+}
+
+// the head of the normalized pattern, computed independently of the crate: zero counts are skipped,
+// and the first word absorbs the following ones as long as they are of the same kind
+fn normalized_head(pattern: &[Op]) -> Option<Op> {
+    let mut ops = pattern
+        .iter()
+        .filter(|op| !matches!(op, Op::Absorb(0) | Op::Squeeze(0)));
+    let mut head = *ops.next()?;
+    for op in ops {
+        head = match (head, op) {
+            (Op::Absorb(n), Op::Absorb(m)) => Op::Absorb(n + m),
+            (Op::Squeeze(n), Op::Squeeze(m)) => Op::Squeeze(n + m),
+            _ => break,
+        };
+    }
+    Some(head)
+}
+
+fn pattern_type(pattern: &[Op]) -> String {
+    let words: Vec<String> = pattern.iter().map(|op| op.to_type()).collect();
+    format!("iopat![{}]", words.join(", "))
+}
+
+// the type of the pattern as a random tree of concatenations, whose leaves may be empty
+fn tree_type(rng: &mut Rng, pattern: &[Op]) -> String {
+    if pattern.is_empty() || rng.below(3) == 0 {
+        return pattern_type(pattern);
+    }
+    let (left, right) = pattern.split_at(rng.below(pattern.len() as u64 + 1) as usize);
+    format!(
+        "Concat<{}, {}>",
+        tree_type(rng, left),
+        tree_type(rng, right)
+    )
+}
+
+const PRELUDE: &str = "\
+use extra_safe::traits::{typenum, Absorb, Concat, Norm, Squeeze, Use};
+use extra_safe::{iopat, ToIOPattern, ToSpongeOp};
+
+fn main() {
+";
+
+// The program checking each pattern of the corpus in turn
+fn corpus() -> String {
+    let mut rng = Rng(0x5afe_5afe_5afe_5afe);
+    let mut out = String::from(PRELUDE);
+
+    for i in 0..CORPUS_SIZE {
+        let pattern = random_pattern(&mut rng);
+        writeln!(
+            out,
+            "    {{\n        // normalize_{i}\n        type P = {};\n        assert_eq!(<Norm<P>>::to_iopattern(), P::to_iopattern().normalize().unwrap());\n    }}",
+            pattern_type(&pattern)
+        )
+        .unwrap();
+    }
+
+    let mut generated = 0;
+    while generated < CORPUS_SIZE {
+        let pattern = random_pattern(&mut rng);
+        let Some(head) = normalized_head(&pattern) else {
+            continue;
+        };
+        let word = match head {
+            Op::Absorb(n) => Op::Absorb(rng.below(n + 1)),
+            Op::Squeeze(n) => Op::Squeeze(rng.below(n + 1)),
+        };
+        writeln!(
+            out,
+            "    {{\n        // use_{generated}\n        type P = {};\n        type W = {};\n        assert_eq!(\n            <Use<Norm<P>, W>>::to_iopattern(),\n            P::to_iopattern().normalize().unwrap().consume(W::SPONGE_OP).unwrap()\n        );\n    }}",
+            tree_type(&mut rng, &pattern),
+            word.to_type()
+        )
+        .unwrap();
+        generated += 1;
+    }
+
+    for i in 0..CORPUS_SIZE {
+        let left = random_pattern(&mut rng);
+        let right = random_pattern(&mut rng);
+        writeln!(
+            out,
+            "    {{\n        // concat_{i}\n        type L = {};\n        type R = {};\n        assert_eq!(\n            <Norm<Concat<L, R>>>::to_iopattern(),\n            L::to_iopattern().concat(&R::to_iopattern()).normalize().unwrap()\n        );\n    }}",
+            tree_type(&mut rng, &left),
+            tree_type(&mut rng, &right)
+        )
+        .unwrap();
+    }
+
+    out.push_str("}\n");
+    out
+}
+
+#[test]
+fn type_level_operations_match_term_level_ones() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("differential.rs");
+    fs::write(&path, corpus()).unwrap();
+    trybuild::TestCases::new().pass(path);
+}